@@ -0,0 +1,98 @@
+/// Server preference order used when the caller does not pass `available`.
+pub(crate) const DEFAULT_PREFERENCE: [&str; 3] = ["br", "zstd", "gzip"];
+
+/// Content codings with a streaming compressor, in lower case.
+pub(crate) const COMPRESSOR_CODINGS: [&str; 4] = ["gzip", "deflate", "br", "zstd"];
+
+pub(crate) fn is_compressor_coding(coding: &str) -> bool {
+    COMPRESSOR_CODINGS.contains(&coding.to_ascii_lowercase().as_str())
+}
+
+struct Preference {
+    coding: String,
+    quality: f32,
+}
+
+fn parse_quality(value: &str) -> Option<f32> {
+    let quality: f32 = value.parse().ok()?;
+    (0.0..=1.0).contains(&quality).then_some(quality)
+}
+
+/// Parses an `Accept-Encoding` field value into lowercased codings and their weights,
+/// skipping malformed elements.
+fn parse_accept_encoding(header: &str) -> Vec<Preference> {
+    let mut preferences = Vec::new();
+    for element in header.split(',') {
+        let mut parts = element.split(';');
+        let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        if coding.is_empty() {
+            continue;
+        }
+        let mut quality = Some(1.0);
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    quality = parse_quality(value.trim());
+                }
+            }
+        }
+        let Some(quality) = quality else {
+            continue;
+        };
+        let coding = match coding.as_str() {
+            "x-gzip" => "gzip".to_string(),
+            "x-compress" => "compress".to_string(),
+            _ => coding,
+        };
+        if preferences
+            .iter()
+            .all(|existing: &Preference| existing.coding != coding)
+        {
+            preferences.push(Preference { coding, quality });
+        }
+    }
+    preferences
+}
+
+fn explicit_quality(preferences: &[Preference], coding: &str) -> Option<f32> {
+    preferences
+        .iter()
+        .find(|preference| preference.coding == coding)
+        .map(|preference| preference.quality)
+}
+
+/// Picks the content coding to use for a response per RFC 9110 section 12.5.3.
+///
+/// The highest client weight wins and ties go to the earliest entry in `available`.
+/// `None` means the response should be sent without a content coding. An unlisted
+/// `identity` takes the lowest weight the client sent, so `gzip;q=0.5` still compresses.
+pub(crate) fn negotiate<'a>(header: &str, available: &[&'a str]) -> Option<&'a str> {
+    let preferences = parse_accept_encoding(header);
+    let wildcard = explicit_quality(&preferences, "*");
+    let lowest = preferences
+        .iter()
+        .map(|preference| preference.quality)
+        .fold(1.0, f32::min);
+    let identity = explicit_quality(&preferences, "identity").unwrap_or(match wildcard {
+        Some(quality) if quality == 0.0 => 0.0,
+        _ => lowest,
+    });
+
+    let mut best: Option<(&'a str, f32)> = None;
+    for &coding in available {
+        let normalized = coding.to_ascii_lowercase();
+        if normalized == "identity" {
+            continue;
+        }
+        let Some(quality) = explicit_quality(&preferences, &normalized).or(wildcard) else {
+            continue;
+        };
+        if quality > 0.0 && best.map_or(true, |(_, current)| quality > current) {
+            best = Some((coding, quality));
+        }
+    }
+    match best {
+        Some((coding, quality)) if quality >= identity => Some(coding),
+        _ => None,
+    }
+}
@@ -0,0 +1,213 @@
+use crate::errors::InvalidParameterError;
+use crate::responder::{media_type, media_type_matches};
+use crate::{check_coding, compress_coding_impl, text_or_bytes, to_bytes};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::collections::HashMap;
+
+/// Number of evenly spaced windows a large input is sampled from, so that headers at the
+/// start of a file (EXIF blocks, archive directories) do not dominate the estimate.
+const SAMPLE_WINDOWS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision {
+    Always,
+    Never,
+    Auto,
+}
+
+impl Decision {
+    fn parse(value: &str) -> PyResult<Self> {
+        match value {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            "auto" => Ok(Self::Auto),
+            _ => Err(InvalidParameterError::new_err(format!(
+                "policy must be 'always', 'never' or 'auto', got {value:?}"
+            ))),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::Never => "never",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+/// Shannon entropy of `sample` in bits per byte, from 0 (constant) to 8 (uniformly random).
+fn entropy(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for byte in sample {
+        counts[*byte as usize] += 1;
+    }
+    let total = sample.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Per-content-type compression rules plus the entropy threshold used for `auto` types.
+/// Rule keys are media types, or a top-level type ending in `/`; exact matches win.
+#[pyclass(module = "fastcomp")]
+#[derive(Clone)]
+pub(crate) struct CompressionPolicy {
+    rules: Vec<(String, Decision)>,
+    default: Decision,
+    #[pyo3(get)]
+    max_entropy: f64,
+    #[pyo3(get)]
+    sample_size: usize,
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            default: Decision::Auto,
+            max_entropy: 7.5,
+            sample_size: 4096,
+        }
+    }
+}
+
+impl CompressionPolicy {
+    pub fn decision(&self, content_type: Option<&[u8]>) -> Decision {
+        let Some(content_type) = content_type else {
+            return self.default;
+        };
+        let media_type = media_type(content_type);
+        let exact = self
+            .rules
+            .iter()
+            .find(|(pattern, _)| *pattern == media_type);
+        exact
+            .or_else(|| {
+                self.rules
+                    .iter()
+                    .filter(|(pattern, _)| media_type_matches(pattern, &media_type))
+                    .max_by_key(|(pattern, _)| pattern.len())
+            })
+            .map_or(self.default, |(_, decision)| *decision)
+    }
+
+    /// Estimates from a sample whether `data` is worth compressing.
+    pub fn compressible(&self, data: &[u8]) -> bool {
+        let estimate = if data.len() <= self.sample_size {
+            entropy(data)
+        } else {
+            let window = (self.sample_size / SAMPLE_WINDOWS).max(1);
+            let stride = (data.len() - window) / (SAMPLE_WINDOWS - 1);
+            let sample: Vec<u8> = (0..SAMPLE_WINDOWS)
+                .flat_map(|index| &data[index * stride..index * stride + window])
+                .copied()
+                .collect();
+            entropy(&sample)
+        };
+        estimate <= self.max_entropy
+    }
+}
+
+#[pymethods]
+impl CompressionPolicy {
+    #[new]
+    #[pyo3(signature = (rules=None, *, default="auto", max_entropy=7.5, sample_size=4096))]
+    fn new(
+        rules: Option<HashMap<String, String>>,
+        default: &str,
+        max_entropy: f64,
+        sample_size: usize,
+    ) -> PyResult<Self> {
+        if !(0.0..=8.0).contains(&max_entropy) {
+            return Err(InvalidParameterError::new_err(
+                "max_entropy must be between 0 and 8",
+            ));
+        }
+        if sample_size == 0 {
+            return Err(InvalidParameterError::new_err(
+                "sample_size must be positive",
+            ));
+        }
+        let rules = rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(pattern, decision)| {
+                Ok((pattern.to_ascii_lowercase(), Decision::parse(&decision)?))
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(Self {
+            rules,
+            default: Decision::parse(default)?,
+            max_entropy,
+            sample_size,
+        })
+    }
+
+    /// Returns `"always"`, `"never"` or `"auto"` for a `Content-Type` value.
+    #[pyo3(signature = (content_type=None))]
+    fn decide(&self, content_type: Option<&PyAny>) -> PyResult<&'static str> {
+        let content_type = content_type.map(text_or_bytes).transpose()?;
+        Ok(self.decision(content_type.as_deref()).as_str())
+    }
+
+    /// Applies the rule for `content_type` and, for `auto` types, the entropy estimate,
+    /// without compressing anything.
+    #[pyo3(signature = (data, content_type=None))]
+    fn should_compress(&self, data: &PyAny, content_type: Option<&PyAny>) -> PyResult<bool> {
+        let content_type = content_type.map(text_or_bytes).transpose()?;
+        Ok(match self.decision(content_type.as_deref()) {
+            Decision::Always => true,
+            Decision::Never => false,
+            Decision::Auto => self.compressible(&to_bytes(data)?),
+        })
+    }
+}
+
+/// Compresses `data` with one codec unless the policy or the entropy estimate says it would
+/// not help, or the result is no smaller. Returns the output and whether it is compressed;
+/// skipped input comes back unchanged. The codec and level are checked even when skipping.
+#[pyfunction]
+#[pyo3(signature = (codec, data, level=None, *, content_type=None, policy=None))]
+pub(crate) fn compress_adaptive(
+    py: Python<'_>,
+    codec: &str,
+    data: &PyAny,
+    level: Option<i32>,
+    content_type: Option<&PyAny>,
+    policy: Option<CompressionPolicy>,
+) -> PyResult<(PyObject, bool)> {
+    check_coding(codec, level)?;
+    let policy = policy.unwrap_or_default();
+    let bytes = to_bytes(data)?;
+    let unchanged = || -> PyObject {
+        match data.downcast::<PyBytes>() {
+            Ok(data) => data.into_py(py),
+            Err(_) => PyBytes::new(py, &bytes).into_py(py),
+        }
+    };
+    let content_type = content_type.map(text_or_bytes).transpose()?;
+    let worthwhile = match policy.decision(content_type.as_deref()) {
+        Decision::Always => true,
+        Decision::Never => false,
+        Decision::Auto => policy.compressible(&bytes),
+    };
+    if !worthwhile {
+        return Ok((unchanged(), false));
+    }
+    let mut output = Vec::new();
+    py.allow_threads(|| compress_coding_impl(codec, &bytes, &mut output, level))?;
+    if output.len() >= bytes.len() {
+        return Ok((unchanged(), false));
+    }
+    Ok((PyBytes::new(py, &output).into_py(py), true))
+}
@@ -0,0 +1,78 @@
+use crate::errors::InvalidParameterError;
+use brotli::enc::backward_references::BrotliEncoderMode;
+use brotli::enc::BrotliEncoderParams;
+use pyo3::prelude::*;
+
+/// Brotli encoder settings shared by `brotli_compress` and `BrotliCompressor`.
+#[pyclass(module = "fastcomp")]
+#[derive(Clone)]
+pub(crate) struct BrotliParams {
+    #[pyo3(get)]
+    lgwin: u32,
+    #[pyo3(get)]
+    lgblock: Option<u32>,
+    #[pyo3(get)]
+    mode: String,
+    #[pyo3(get)]
+    size_hint: usize,
+}
+
+impl Default for BrotliParams {
+    fn default() -> Self {
+        Self {
+            lgwin: 22,
+            lgblock: None,
+            mode: "generic".to_string(),
+            size_hint: 0,
+        }
+    }
+}
+
+impl BrotliParams {
+    /// Builds encoder parameters for `quality`, which callers check with `check_level`
+    /// since the encoder would clamp it.
+    pub fn encoder_params(&self, quality: u32) -> BrotliEncoderParams {
+        let mode = match self.mode.as_str() {
+            "text" => BrotliEncoderMode::BROTLI_MODE_TEXT,
+            "font" => BrotliEncoderMode::BROTLI_MODE_FONT,
+            _ => BrotliEncoderMode::BROTLI_MODE_GENERIC,
+        };
+        BrotliEncoderParams {
+            quality: quality as i32,
+            lgwin: self.lgwin as i32,
+            lgblock: self.lgblock.unwrap_or(0) as i32,
+            mode,
+            size_hint: self.size_hint,
+            ..BrotliEncoderParams::default()
+        }
+    }
+}
+
+#[pymethods]
+impl BrotliParams {
+    #[new]
+    #[pyo3(signature = (*, lgwin=22, lgblock=None, mode="generic", size_hint=0))]
+    fn new(lgwin: u32, lgblock: Option<u32>, mode: &str, size_hint: usize) -> PyResult<Self> {
+        if !(10..=24).contains(&lgwin) {
+            return Err(InvalidParameterError::new_err(
+                "lgwin must be between 10 and 24",
+            ));
+        }
+        if lgblock.is_some_and(|lgblock| !(16..=24).contains(&lgblock)) {
+            return Err(InvalidParameterError::new_err(
+                "lgblock must be between 16 and 24",
+            ));
+        }
+        if !matches!(mode, "generic" | "text" | "font") {
+            return Err(InvalidParameterError::new_err(
+                "mode must be one of 'generic', 'text' or 'font'",
+            ));
+        }
+        Ok(Self {
+            lgwin,
+            lgblock,
+            mode: mode.to_string(),
+            size_hint,
+        })
+    }
+}
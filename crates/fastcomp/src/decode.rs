@@ -0,0 +1,821 @@
+use crate::output::Output;
+use brotli::enc::StandardAlloc;
+use brotli::{BrotliDecompressStream, BrotliResult, BrotliState};
+use flate2::{Crc, Decompress, FlushDecompress, Status};
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::mem;
+use std::sync::Arc;
+use twox_hash::XxHash32;
+use zstd::dict::DecoderDictionary;
+use zstd::stream::raw::Operation;
+
+const CHUNK_SIZE: usize = 32 * 1024;
+
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+const GZIP_RESERVED: u8 = 0xe0;
+
+/// Result of a single decoder step.
+pub(crate) struct Step {
+    pub consumed: usize,
+    pub produced: usize,
+    pub finished: bool,
+}
+
+/// Push-style decoder that never writes past the end of `output`.
+pub(crate) trait StreamDecoder: Send {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step>;
+}
+
+pub(crate) struct Progress {
+    pub consumed: usize,
+    pub finished: bool,
+    /// Set when the decoder rejected the input; `consumed` stops at the failing step.
+    pub error: Option<io::Error>,
+}
+
+/// Runs `decoder` over `input`, appending to `output`, until it finishes, stalls, fails or
+/// `output` holds `limit` bytes.
+pub(crate) fn drive(
+    decoder: &mut dyn StreamDecoder,
+    input: &[u8],
+    output: &mut impl Output,
+    limit: usize,
+) -> Progress {
+    let mut consumed = 0;
+    loop {
+        let start = output.len();
+        let room = CHUNK_SIZE.min(limit - start);
+        if room == 0 {
+            break;
+        }
+        let step = match decoder.step(&input[consumed..], output.grow(room)) {
+            Ok(step) => step,
+            Err(err) => {
+                output.truncate(start);
+                return Progress {
+                    consumed,
+                    finished: false,
+                    error: Some(err),
+                };
+            }
+        };
+        output.truncate(start + step.produced);
+        consumed += step.consumed;
+        if step.finished {
+            return Progress {
+                consumed,
+                finished: true,
+                error: None,
+            };
+        }
+        if step.consumed == 0 && step.produced == 0 {
+            break;
+        }
+    }
+    Progress {
+        consumed,
+        finished: false,
+        error: None,
+    }
+}
+
+/// Reads `reader` to the end into `output`, stopping once it holds `limit` bytes.
+pub(crate) fn read_into(
+    mut reader: impl Read,
+    output: &mut impl Output,
+    limit: usize,
+) -> io::Result<()> {
+    loop {
+        let start = output.len();
+        let room = CHUNK_SIZE.min(limit - start);
+        if room == 0 {
+            return Ok(());
+        }
+        match reader.read(output.grow(room)) {
+            Ok(0) => {
+                output.truncate(start);
+                return Ok(());
+            }
+            Ok(read) => output.truncate(start + read),
+            Err(err) => {
+                output.truncate(start);
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn skip_zero_terminated(buf: &[u8], offset: usize) -> Option<usize> {
+    buf.get(offset..)?
+        .iter()
+        .position(|byte| *byte == 0)
+        .map(|pos| offset + pos + 1)
+}
+
+/// Returns the length of the gzip member header at the start of `buf`, or `None` if more
+/// bytes are needed to tell.
+pub(crate) fn gzip_header_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    if buf.len() < 10 {
+        if buf.iter().zip([0x1f, 0x8b, 8]).any(|(a, b)| *a != b) {
+            return Err(invalid_data("invalid gzip header"));
+        }
+        return Ok(None);
+    }
+    if buf[0] != 0x1f || buf[1] != 0x8b || buf[2] != 8 || buf[3] & GZIP_RESERVED != 0 {
+        return Err(invalid_data("invalid gzip header"));
+    }
+    let flags = buf[3];
+    let mut offset = 10;
+    if flags & GZIP_FEXTRA != 0 {
+        let Some(len) = buf.get(offset..offset + 2) else {
+            return Ok(None);
+        };
+        offset += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    if flags & GZIP_FNAME != 0 {
+        let Some(end) = skip_zero_terminated(buf, offset) else {
+            return Ok(None);
+        };
+        offset = end;
+    }
+    if flags & GZIP_FCOMMENT != 0 {
+        let Some(end) = skip_zero_terminated(buf, offset) else {
+            return Ok(None);
+        };
+        offset = end;
+    }
+    if flags & GZIP_FHCRC != 0 {
+        offset += 2;
+    }
+    if buf.len() < offset {
+        return Ok(None);
+    }
+    Ok(Some(offset))
+}
+
+enum GzipStage {
+    Header(Vec<u8>),
+    Body,
+    Trailer(Vec<u8>),
+    Done,
+}
+
+/// Decodes a single gzip member, verifying the CRC-32 and length trailer.
+pub(crate) struct GzipStreamDecoder {
+    stage: GzipStage,
+    inflate: Decompress,
+    crc: Crc,
+}
+
+impl GzipStreamDecoder {
+    pub fn new() -> Self {
+        Self {
+            stage: GzipStage::Header(Vec::new()),
+            inflate: Decompress::new(false),
+            crc: Crc::new(),
+        }
+    }
+}
+
+impl StreamDecoder for GzipStreamDecoder {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step> {
+        match &mut self.stage {
+            GzipStage::Header(buf) => {
+                let buffered = buf.len();
+                buf.extend_from_slice(input);
+                let Some(len) = gzip_header_len(buf)? else {
+                    return Ok(Step {
+                        consumed: input.len(),
+                        produced: 0,
+                        finished: false,
+                    });
+                };
+                self.stage = GzipStage::Body;
+                Ok(Step {
+                    consumed: len - buffered,
+                    produced: 0,
+                    finished: false,
+                })
+            }
+            GzipStage::Body => {
+                let total_in = self.inflate.total_in();
+                let total_out = self.inflate.total_out();
+                let status = self
+                    .inflate
+                    .decompress(input, output, FlushDecompress::None)?;
+                let consumed = (self.inflate.total_in() - total_in) as usize;
+                let produced = (self.inflate.total_out() - total_out) as usize;
+                self.crc.update(&output[..produced]);
+                if status == Status::StreamEnd {
+                    self.stage = GzipStage::Trailer(Vec::with_capacity(8));
+                }
+                Ok(Step {
+                    consumed,
+                    produced,
+                    finished: false,
+                })
+            }
+            GzipStage::Trailer(buf) => {
+                let take = (8 - buf.len()).min(input.len());
+                buf.extend_from_slice(&input[..take]);
+                if buf.len() < 8 {
+                    return Ok(Step {
+                        consumed: take,
+                        produced: 0,
+                        finished: false,
+                    });
+                }
+                let crc = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+                let size = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+                if crc != self.crc.sum() || size != self.crc.amount() {
+                    return Err(invalid_data("corrupt gzip trailer"));
+                }
+                self.stage = GzipStage::Done;
+                Ok(Step {
+                    consumed: take,
+                    produced: 0,
+                    finished: true,
+                })
+            }
+            GzipStage::Done => Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: true,
+            }),
+        }
+    }
+}
+
+/// Returns true when `data` starts with a valid RFC 1950 zlib header rather than a raw
+/// deflate block.
+pub(crate) fn is_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => {
+            cmf & 0x0f == 8 && cmf >> 4 <= 7 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Guesses the coding of `data` from its magic bytes. Brotli and raw Snappy have no magic
+/// number and are never detected.
+pub(crate) fn detect_coding(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0x1f, 0x8b]) {
+        Some("gzip")
+    } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some("zstd")
+    } else if data.starts_with(&LZ4_MAGIC.to_le_bytes()) {
+        Some("lz4")
+    } else if data.starts_with(&SNAPPY_STREAM_IDENTIFIER) {
+        Some("snappy")
+    } else if is_zlib_header(data) {
+        Some("deflate")
+    } else {
+        None
+    }
+}
+
+/// Decodes an HTTP `deflate` body, accepting both zlib-wrapped and raw deflate framing.
+pub(crate) struct DeflateStreamDecoder {
+    prefix: Vec<u8>,
+    inflate: Option<Decompress>,
+    finished: bool,
+}
+
+impl DeflateStreamDecoder {
+    pub fn new() -> Self {
+        Self {
+            prefix: Vec::with_capacity(2),
+            inflate: None,
+            finished: false,
+        }
+    }
+
+    fn inflate(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        let inflate = self.inflate.as_mut().expect("framing detected");
+        let total_in = inflate.total_in();
+        let total_out = inflate.total_out();
+        let status = inflate.decompress(input, output, FlushDecompress::None)?;
+        self.finished = status == Status::StreamEnd;
+        Ok((
+            (inflate.total_in() - total_in) as usize,
+            (inflate.total_out() - total_out) as usize,
+        ))
+    }
+}
+
+impl StreamDecoder for DeflateStreamDecoder {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step> {
+        if self.finished {
+            return Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: true,
+            });
+        }
+        let mut produced = 0;
+        if self.inflate.is_none() {
+            if self.prefix.len() + input.len() < 2 {
+                self.prefix.extend_from_slice(input);
+                return Ok(Step {
+                    consumed: input.len(),
+                    produced: 0,
+                    finished: false,
+                });
+            }
+            let mut head = self.prefix.clone();
+            head.extend_from_slice(&input[..2 - self.prefix.len()]);
+            self.inflate = Some(Decompress::new(is_zlib_header(&head)));
+            let prefix = mem::take(&mut self.prefix);
+            if !prefix.is_empty() {
+                let (consumed, written) = self.inflate(&prefix, output)?;
+                if consumed != prefix.len() {
+                    return Err(invalid_data("corrupt deflate stream"));
+                }
+                produced = written;
+            }
+        }
+        let (consumed, written) = self.inflate(input, &mut output[produced..])?;
+        Ok(Step {
+            consumed,
+            produced: produced + written,
+            finished: self.finished,
+        })
+    }
+}
+
+pub(crate) struct BrotliStreamDecoder {
+    state: BrotliState<StandardAlloc, StandardAlloc, StandardAlloc>,
+    total_out: usize,
+    finished: bool,
+}
+
+impl BrotliStreamDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: BrotliState::new(
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+            ),
+            total_out: 0,
+            finished: false,
+        }
+    }
+
+    /// Decodes a stream compressed against a custom dictionary, as in the `dcb` coding.
+    pub fn with_dictionary(dictionary: Vec<u8>) -> Self {
+        Self {
+            state: BrotliState::new_with_custom_dictionary(
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                StandardAlloc::default(),
+                dictionary.into(),
+            ),
+            total_out: 0,
+            finished: false,
+        }
+    }
+}
+
+impl StreamDecoder for BrotliStreamDecoder {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step> {
+        if self.finished {
+            return Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: true,
+            });
+        }
+        let mut available_in = input.len();
+        let mut input_offset = 0;
+        let mut available_out = output.len();
+        let mut output_offset = 0;
+        let result = BrotliDecompressStream(
+            &mut available_in,
+            &mut input_offset,
+            input,
+            &mut available_out,
+            &mut output_offset,
+            output,
+            &mut self.total_out,
+            &mut self.state,
+        );
+        if let BrotliResult::ResultFailure = result {
+            return Err(invalid_data("corrupt brotli stream"));
+        }
+        self.finished = matches!(result, BrotliResult::ResultSuccess);
+        Ok(Step {
+            consumed: input_offset,
+            produced: output_offset,
+            finished: self.finished,
+        })
+    }
+}
+
+/// Decodes a single zstd frame.
+pub(crate) struct ZstdStreamDecoder<'a> {
+    decoder: zstd::stream::raw::Decoder<'a>,
+    // Keeps the prepared dictionary referenced by `decoder` alive; dropped after it.
+    _dictionary: Option<Arc<DecoderDictionary<'static>>>,
+    finished: bool,
+}
+
+impl ZstdStreamDecoder<'static> {
+    pub fn new(dictionary: Option<Arc<DecoderDictionary<'static>>>) -> io::Result<Self> {
+        let decoder = match &dictionary {
+            Some(dictionary) => zstd::stream::raw::Decoder::with_prepared_dictionary(dictionary)?,
+            None => zstd::stream::raw::Decoder::new()?,
+        };
+        Ok(Self {
+            decoder,
+            _dictionary: dictionary,
+            finished: false,
+        })
+    }
+}
+
+impl<'a> ZstdStreamDecoder<'a> {
+    /// Decodes one frame with `prefix` loaded as raw content, never parsed as a structured
+    /// zstd dictionary.
+    pub fn with_prefix(prefix: &'a [u8]) -> io::Result<Self> {
+        Ok(Self {
+            decoder: zstd::stream::raw::Decoder::with_ref_prefix(prefix)?,
+            _dictionary: None,
+            finished: false,
+        })
+    }
+}
+
+impl StreamDecoder for ZstdStreamDecoder<'_> {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step> {
+        if self.finished {
+            return Ok(Step {
+                consumed: 0,
+                produced: 0,
+                finished: true,
+            });
+        }
+        let status = self.decoder.run_on_buffers(input, output)?;
+        self.finished = status.remaining == 0;
+        Ok(Step {
+            consumed: status.bytes_read,
+            produced: status.bytes_written,
+            finished: self.finished,
+        })
+    }
+}
+
+/// Framing of a format whose data only decodes in whole units (blocks or chunks).
+pub(crate) trait BlockFormat: Send {
+    /// Number of bytes the unit starting with `head` needs. Returning a value no larger than
+    /// `head.len()` means the unit is complete.
+    fn unit_len(&self, head: &[u8]) -> io::Result<usize>;
+
+    /// Decodes one complete unit into `out`, returning true when the stream has ended.
+    fn decode_unit(&mut self, unit: &[u8], out: &mut Vec<u8>) -> io::Result<bool>;
+
+    /// Number of bytes following the unit just decoded that are discarded as they arrive
+    /// rather than buffered, for padding and skippable frames.
+    fn take_skip(&mut self) -> usize {
+        0
+    }
+}
+
+/// Adapts a `BlockFormat` to `StreamDecoder` by buffering partial units and holding decoded
+/// bytes that did not fit in the caller's output.
+pub(crate) struct BlockStreamDecoder<F> {
+    format: F,
+    unit: Vec<u8>,
+    skip: usize,
+    decoded: Vec<u8>,
+    offset: usize,
+    finished: bool,
+}
+
+impl<F: BlockFormat> BlockStreamDecoder<F> {
+    pub fn new(format: F) -> Self {
+        Self {
+            format,
+            unit: Vec::new(),
+            skip: 0,
+            decoded: Vec::new(),
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    fn drain(&mut self, output: &mut [u8]) -> usize {
+        let len = (self.decoded.len() - self.offset).min(output.len());
+        output[..len].copy_from_slice(&self.decoded[self.offset..self.offset + len]);
+        self.offset += len;
+        if self.offset == self.decoded.len() {
+            self.decoded.clear();
+            self.offset = 0;
+        }
+        len
+    }
+}
+
+impl<F: BlockFormat> StreamDecoder for BlockStreamDecoder<F> {
+    fn step(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<Step> {
+        let mut consumed = 0;
+        let mut produced = self.drain(output);
+        while self.decoded.is_empty() && !self.finished {
+            let skipped = self.skip.min(input.len() - consumed);
+            self.skip -= skipped;
+            consumed += skipped;
+            if self.skip > 0 {
+                break;
+            }
+            let mut need = self.format.unit_len(&self.unit)?;
+            while self.unit.len() < need && consumed < input.len() {
+                let take = (need - self.unit.len()).min(input.len() - consumed);
+                self.unit
+                    .extend_from_slice(&input[consumed..consumed + take]);
+                consumed += take;
+                need = self.format.unit_len(&self.unit)?;
+            }
+            if self.unit.len() < need {
+                break;
+            }
+            self.finished = self.format.decode_unit(&self.unit, &mut self.decoded)?;
+            self.skip = self.format.take_skip();
+            self.unit.clear();
+            produced += self.drain(&mut output[produced..]);
+        }
+        Ok(Step {
+            consumed,
+            produced,
+            finished: self.finished && self.decoded.is_empty(),
+        })
+    }
+}
+
+const LZ4_MAGIC: u32 = 0x184d_2204;
+const LZ4_SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+const LZ4_INDEPENDENT_BLOCKS: u8 = 0x20;
+const LZ4_BLOCK_CHECKSUM: u8 = 0x10;
+const LZ4_CONTENT_SIZE: u8 = 0x08;
+const LZ4_CONTENT_CHECKSUM: u8 = 0x04;
+const LZ4_DICT_ID: u8 = 0x01;
+const LZ4_UNCOMPRESSED_BLOCK: u32 = 0x8000_0000;
+/// Linked blocks may reference this much of the preceding output.
+const LZ4_WINDOW: usize = 64 * 1024;
+
+fn read_u32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+/// Body length of the skippable frame whose 8-byte header is `header`, if it is one.
+pub(crate) fn lz4_skippable_frame(header: &[u8]) -> Option<usize> {
+    (read_u32(header) & 0xffff_fff0 == LZ4_SKIPPABLE_MAGIC).then(|| read_u32(&header[4..]) as usize)
+}
+
+fn xxh32(data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(0);
+    hasher.write(data);
+    hasher.finish() as u32
+}
+
+enum Lz4Stage {
+    Magic,
+    Descriptor,
+    Blocks,
+    ContentChecksum,
+}
+
+/// Decodes a single LZ4 frame, skipping any skippable frames in front of it.
+pub(crate) struct Lz4Format {
+    stage: Lz4Stage,
+    flags: u8,
+    max_block_size: usize,
+    content_hasher: XxHash32,
+    window: Vec<u8>,
+    skip: usize,
+}
+
+impl Lz4Format {
+    pub fn new() -> Self {
+        Self {
+            stage: Lz4Stage::Magic,
+            flags: 0,
+            max_block_size: 0,
+            content_hasher: XxHash32::with_seed(0),
+            window: Vec::new(),
+            skip: 0,
+        }
+    }
+
+    fn descriptor(&mut self, unit: &[u8]) -> io::Result<()> {
+        let (flags, bd) = (unit[0], unit[1]);
+        if flags >> 6 != 1 || flags & 0x02 != 0 || bd & 0x8f != 0 {
+            return Err(invalid_data("invalid lz4 frame descriptor"));
+        }
+        if flags & LZ4_DICT_ID != 0 {
+            return Err(invalid_data(
+                "lz4 frames with a dictionary are not supported",
+            ));
+        }
+        let (descriptor, checksum) = unit.split_at(unit.len() - 1);
+        if (xxh32(descriptor) >> 8) as u8 != checksum[0] {
+            return Err(invalid_data("corrupt lz4 frame descriptor"));
+        }
+        self.max_block_size = match bd >> 4 {
+            4 => 64 * 1024,
+            5 => 256 * 1024,
+            6 => 1024 * 1024,
+            7 => 4 * 1024 * 1024,
+            _ => return Err(invalid_data("invalid lz4 block size")),
+        };
+        self.flags = flags;
+        Ok(())
+    }
+
+    fn block(&mut self, unit: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        let word = read_u32(unit);
+        let len = (word & !LZ4_UNCOMPRESSED_BLOCK) as usize;
+        let block = &unit[4..4 + len];
+        if self.flags & LZ4_BLOCK_CHECKSUM != 0 && xxh32(block) != read_u32(&unit[4 + len..]) {
+            return Err(invalid_data("lz4 block checksum mismatch"));
+        }
+        let start = out.len();
+        if word & LZ4_UNCOMPRESSED_BLOCK != 0 {
+            out.extend_from_slice(block);
+        } else {
+            out.resize(start + self.max_block_size, 0);
+            let written = if self.flags & LZ4_INDEPENDENT_BLOCKS != 0 {
+                lz4_flex::block::decompress_into(block, &mut out[start..])
+            } else {
+                lz4_flex::block::decompress_into_with_dict(block, &mut out[start..], &self.window)
+            }
+            .map_err(|err| invalid_data(&format!("corrupt lz4 block: {err}")))?;
+            out.truncate(start + written);
+        }
+        let decoded = &out[start..];
+        if self.flags & LZ4_CONTENT_CHECKSUM != 0 {
+            self.content_hasher.write(decoded);
+        }
+        if self.flags & LZ4_INDEPENDENT_BLOCKS == 0 {
+            self.window.extend_from_slice(decoded);
+            let excess = self.window.len().saturating_sub(LZ4_WINDOW);
+            self.window.drain(..excess);
+        }
+        Ok(())
+    }
+}
+
+impl BlockFormat for Lz4Format {
+    fn unit_len(&self, head: &[u8]) -> io::Result<usize> {
+        if let Lz4Stage::Descriptor = self.stage {
+            // FLG, BD and the header checksum, plus whatever optional fields FLG announces.
+            let Some(flags) = head.first() else {
+                return Ok(3);
+            };
+            let mut len = 3;
+            if flags & LZ4_CONTENT_SIZE != 0 {
+                len += 8;
+            }
+            if flags & LZ4_DICT_ID != 0 {
+                len += 4;
+            }
+            return Ok(len);
+        }
+        if head.len() < 4 {
+            return Ok(4);
+        }
+        Ok(match self.stage {
+            // Only the header of a skippable frame is buffered; its body is skipped.
+            Lz4Stage::Magic if read_u32(head) & 0xffff_fff0 == LZ4_SKIPPABLE_MAGIC => 8,
+            Lz4Stage::Blocks => {
+                let len = (read_u32(head) & !LZ4_UNCOMPRESSED_BLOCK) as usize;
+                if len == 0 {
+                    4
+                } else if len > self.max_block_size {
+                    return Err(invalid_data("lz4 block exceeds the frame's block size"));
+                } else if self.flags & LZ4_BLOCK_CHECKSUM != 0 {
+                    8 + len
+                } else {
+                    4 + len
+                }
+            }
+            _ => 4,
+        })
+    }
+
+    fn decode_unit(&mut self, unit: &[u8], out: &mut Vec<u8>) -> io::Result<bool> {
+        match self.stage {
+            Lz4Stage::Magic => match read_u32(unit) {
+                LZ4_MAGIC => self.stage = Lz4Stage::Descriptor,
+                _ => match lz4_skippable_frame(unit) {
+                    Some(len) => self.skip = len,
+                    None => return Err(invalid_data("invalid lz4 frame magic")),
+                },
+            },
+            Lz4Stage::Descriptor => {
+                self.descriptor(unit)?;
+                self.stage = Lz4Stage::Blocks;
+            }
+            Lz4Stage::Blocks if read_u32(unit) == 0 => {
+                if self.flags & LZ4_CONTENT_CHECKSUM == 0 {
+                    return Ok(true);
+                }
+                self.stage = Lz4Stage::ContentChecksum;
+            }
+            Lz4Stage::Blocks => self.block(unit, out)?,
+            Lz4Stage::ContentChecksum => {
+                if self.content_hasher.finish() as u32 != read_u32(unit) {
+                    return Err(invalid_data("lz4 content checksum mismatch"));
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn take_skip(&mut self) -> usize {
+        mem::take(&mut self.skip)
+    }
+}
+
+const SNAPPY_STREAM_IDENTIFIER: [u8; 10] = *b"\xff\x06\x00\x00sNaPpY";
+/// Longest chunk body snap's frame reader accepts, whatever the chunk type.
+const SNAPPY_MAX_CHUNK_LEN: usize = 76_490;
+
+fn read_u24(buf: &[u8]) -> usize {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], 0]) as usize
+}
+
+/// Decodes the Snappy framing format. The format has no end marker, so the stream never
+/// reports that it has finished. Chunks are held to the same rules as snap's frame reader,
+/// which the one-shot path uses.
+pub(crate) struct SnappyFormat {
+    started: bool,
+    skip: usize,
+}
+
+impl SnappyFormat {
+    pub fn new() -> Self {
+        Self {
+            started: false,
+            skip: 0,
+        }
+    }
+}
+
+impl BlockFormat for SnappyFormat {
+    fn unit_len(&self, head: &[u8]) -> io::Result<usize> {
+        if head.len() < 4 {
+            return Ok(4);
+        }
+        if !self.started && head[0] != 0xff {
+            return Err(invalid_data("missing snappy stream identifier"));
+        }
+        let len = read_u24(&head[1..]);
+        if len > SNAPPY_MAX_CHUNK_LEN {
+            return Err(invalid_data("snappy chunk is too long"));
+        }
+        Ok(match head[0] {
+            0x02..=0x7f => return Err(invalid_data("reserved snappy chunk type")),
+            // Padding and reserved skippable chunks are discarded rather than buffered.
+            0x80..=0xfe => 4,
+            _ => 4 + len,
+        })
+    }
+
+    fn decode_unit(&mut self, unit: &[u8], out: &mut Vec<u8>) -> io::Result<bool> {
+        match unit[0] {
+            0xff if unit != SNAPPY_STREAM_IDENTIFIER => {
+                return Err(invalid_data("invalid snappy stream identifier"));
+            }
+            0xff => {
+                self.started = true;
+                return Ok(false);
+            }
+            0x80..=0xfe => {
+                self.skip = read_u24(&unit[1..]);
+                return Ok(false);
+            }
+            _ => {}
+        }
+        // snap verifies the chunk's masked CRC-32C, so each chunk is replayed through its
+        // frame reader behind a fresh stream identifier.
+        let mut reader = snap::read::FrameDecoder::new((&SNAPPY_STREAM_IDENTIFIER[..]).chain(unit));
+        reader.read_to_end(out)?;
+        Ok(false)
+    }
+
+    fn take_skip(&mut self) -> usize {
+        mem::take(&mut self.skip)
+    }
+}
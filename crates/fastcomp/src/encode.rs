@@ -0,0 +1,281 @@
+use crate::brotli_params::BrotliParams;
+use crate::seekable::SeekableEncoder;
+use crate::zstd_params::ZstdParams;
+use brotli::enc::BrotliEncoderParams;
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use std::io::{self, Write};
+use std::sync::Arc;
+use zstd::dict::EncoderDictionary;
+
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// How far `StreamEncoder::flush` goes. A full flush also resets the deflate history so
+/// decoding can restart from that point; the other codecs treat it like a sync flush.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlushMode {
+    Sync,
+    Full,
+}
+
+impl FlushMode {
+    pub fn parse(mode: &str) -> io::Result<Self> {
+        match mode {
+            "sync" => Ok(Self::Sync),
+            "full" => Ok(Self::Full),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported flush mode: {mode}"),
+            )),
+        }
+    }
+}
+
+/// Optional gzip member header fields. `filename` and `comment` are ISO 8859-1 bytes
+/// without the terminating NUL.
+#[derive(Default)]
+pub(crate) struct GzipHeader {
+    pub filename: Option<Vec<u8>>,
+    pub comment: Option<Vec<u8>>,
+    pub mtime: u32,
+}
+
+impl GzipHeader {
+    fn to_bytes(&self, level: u32) -> Vec<u8> {
+        let mut flags = 0;
+        if self.filename.is_some() {
+            flags |= 0x08;
+        }
+        if self.comment.is_some() {
+            flags |= 0x10;
+        }
+        let xfl = match level {
+            9.. => 2,
+            0 | 1 => 4,
+            _ => 0,
+        };
+        let mut header = vec![0x1f, 0x8b, 8, flags];
+        header.extend_from_slice(&self.mtime.to_le_bytes());
+        header.extend_from_slice(&[xfl, 255]);
+        for field in [&self.filename, &self.comment].into_iter().flatten() {
+            header.extend_from_slice(field);
+            header.push(0);
+        }
+        header
+    }
+}
+
+/// Deflate encoder driven directly through `Compress` so that sync and full flushes are
+/// available. `crc` is set when the output is a gzip member, whose header and trailer are
+/// written here rather than by zlib.
+pub(crate) struct FlateEncoder {
+    compress: Compress,
+    crc: Option<Crc>,
+    output: Vec<u8>,
+}
+
+impl FlateEncoder {
+    pub fn gzip(level: u32, header: &GzipHeader) -> Self {
+        Self {
+            compress: Compress::new(Compression::new(level), false),
+            crc: Some(Crc::new()),
+            output: header.to_bytes(level),
+        }
+    }
+
+    pub fn deflate(level: u32, raw: bool) -> Self {
+        Self {
+            compress: Compress::new(Compression::new(level), !raw),
+            crc: None,
+            output: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, mut input: &[u8], flush: FlushCompress) -> io::Result<()> {
+        loop {
+            self.output.reserve(CHUNK_SIZE);
+            let total_in = self.compress.total_in();
+            let status = self.compress.compress_vec(input, &mut self.output, flush)?;
+            input = &input[(self.compress.total_in() - total_in) as usize..];
+            // zlib is done with a flush once it leaves output space unused.
+            let drained = input.is_empty() && self.output.len() < self.output.capacity();
+            if status == Status::StreamEnd || (drained && flush != FlushCompress::Finish) {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if let Some(crc) = &mut self.crc {
+            crc.update(data);
+        }
+        self.run(data, FlushCompress::None)
+    }
+
+    pub fn flush(&mut self, mode: FlushMode) -> io::Result<()> {
+        match mode {
+            FlushMode::Sync => self.run(&[], FlushCompress::Sync),
+            FlushMode::Full => self.run(&[], FlushCompress::Full),
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        self.run(&[], FlushCompress::Finish)?;
+        if let Some(crc) = &self.crc {
+            self.output.extend_from_slice(&crc.sum().to_le_bytes());
+            self.output.extend_from_slice(&crc.amount().to_le_bytes());
+        }
+        Ok(self.output)
+    }
+
+    /// Compresses all of `data` into `out`, handing output over a chunk at a time so that
+    /// the whole result is never buffered here.
+    pub fn compress_to(mut self, data: &[u8], out: &mut impl Write) -> io::Result<()> {
+        for chunk in data.chunks(CHUNK_SIZE) {
+            self.write(chunk)?;
+            out.write_all(&self.output)?;
+            self.output.clear();
+        }
+        out.write_all(&self.finish()?)
+    }
+}
+
+/// Incremental encoder that hands back whatever compressed bytes each call produced.
+pub(crate) enum StreamEncoder {
+    Gzip(FlateEncoder),
+    Deflate(FlateEncoder),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+    /// The encoder only borrows a prepared dictionary, so the `Arc` keeps it alive and is
+    /// declared second so that it is dropped after the encoder.
+    Zstd(
+        zstd::stream::write::Encoder<'static, Vec<u8>>,
+        Option<Arc<EncoderDictionary<'static>>>,
+    ),
+    Lz4(Box<lz4_flex::frame::FrameEncoder<Vec<u8>>>),
+    Snappy(Box<snap::write::FrameEncoder<Vec<u8>>>),
+    ZstdSeekable(Box<SeekableEncoder>),
+}
+
+impl StreamEncoder {
+    pub fn gzip(level: u32) -> Self {
+        Self::gzip_with_header(level, &GzipHeader::default())
+    }
+
+    pub fn gzip_with_header(level: u32, header: &GzipHeader) -> Self {
+        Self::Gzip(FlateEncoder::gzip(level, header))
+    }
+
+    pub fn deflate(level: u32, raw: bool) -> Self {
+        Self::Deflate(FlateEncoder::deflate(level, raw))
+    }
+
+    pub fn brotli(params: &BrotliEncoderParams) -> Self {
+        let writer = brotli::CompressorWriter::with_params(Vec::new(), 4096, params);
+        Self::Brotli(Box::new(writer))
+    }
+
+    pub fn zstd(level: i32, params: &ZstdParams) -> io::Result<Self> {
+        let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), level)?;
+        params.apply(&mut encoder, None)?;
+        Ok(Self::Zstd(encoder, None))
+    }
+
+    pub fn zstd_with_dictionary(
+        dictionary: Arc<EncoderDictionary<'static>>,
+        params: &ZstdParams,
+    ) -> io::Result<Self> {
+        let mut encoder =
+            zstd::stream::write::Encoder::with_prepared_dictionary(Vec::new(), &dictionary)?;
+        params.apply(&mut encoder, None)?;
+        Ok(Self::Zstd(encoder, Some(dictionary)))
+    }
+
+    pub fn lz4() -> Self {
+        let info = lz4_flex::frame::FrameInfo::new().content_checksum(true);
+        let encoder = lz4_flex::frame::FrameEncoder::with_frame_info(info, Vec::new());
+        Self::Lz4(Box::new(encoder))
+    }
+
+    pub fn snappy() -> Self {
+        Self::Snappy(Box::new(snap::write::FrameEncoder::new(Vec::new())))
+    }
+
+    /// Independent zstd frames of at most `frame_size` input bytes followed by a seek table.
+    /// A flush ends the current frame.
+    pub fn zstd_seekable(level: i32, frame_size: usize, checksum: bool) -> io::Result<Self> {
+        let encoder = SeekableEncoder::new(level, frame_size, checksum)?;
+        Ok(Self::ZstdSeekable(Box::new(encoder)))
+    }
+
+    /// Builds an encoder with the default level for an HTTP content coding token.
+    pub fn for_coding(coding: &str) -> io::Result<Self> {
+        match coding.to_ascii_lowercase().as_str() {
+            "gzip" => Ok(Self::gzip(5)),
+            "deflate" => Ok(Self::deflate(5, false)),
+            "br" => Ok(Self::brotli(&BrotliParams::default().encoder_params(5))),
+            "zstd" => Self::zstd(3, &ZstdParams::default()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported content coding: {coding}"),
+            )),
+        }
+    }
+
+    /// Content coding token of the encoder, as used in error reports.
+    pub fn coding(&self) -> &'static str {
+        match self {
+            Self::Gzip(_) => "gzip",
+            Self::Deflate(_) => "deflate",
+            Self::Brotli(_) => "br",
+            Self::Zstd(..) | Self::ZstdSeekable(_) => "zstd",
+            Self::Lz4(_) => "lz4",
+            Self::Snappy(_) => "snappy",
+        }
+    }
+
+    fn output(&mut self) -> &mut Vec<u8> {
+        match self {
+            Self::Gzip(encoder) | Self::Deflate(encoder) => &mut encoder.output,
+            Self::Brotli(writer) => writer.get_mut(),
+            Self::Zstd(encoder, _) => encoder.get_mut(),
+            Self::Lz4(encoder) => encoder.get_mut(),
+            Self::Snappy(encoder) => encoder.get_mut(),
+            Self::ZstdSeekable(encoder) => &mut encoder.output,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(encoder) | Self::Deflate(encoder) => encoder.write(data)?,
+            Self::Brotli(writer) => writer.write_all(data)?,
+            Self::Zstd(encoder, _) => encoder.write_all(data)?,
+            Self::Lz4(encoder) => encoder.write_all(data)?,
+            Self::Snappy(encoder) => encoder.write_all(data)?,
+            Self::ZstdSeekable(encoder) => encoder.write(data)?,
+        }
+        Ok(std::mem::take(self.output()))
+    }
+
+    /// Emits everything needed to decode the input seen so far without ending the stream.
+    pub fn flush(&mut self, mode: FlushMode) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(encoder) | Self::Deflate(encoder) => encoder.flush(mode)?,
+            Self::Brotli(writer) => writer.flush()?,
+            Self::Zstd(encoder, _) => encoder.flush()?,
+            Self::Lz4(encoder) => encoder.flush()?,
+            Self::Snappy(encoder) => encoder.flush()?,
+            Self::ZstdSeekable(encoder) => encoder.flush()?,
+        }
+        Ok(std::mem::take(self.output()))
+    }
+
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Gzip(encoder) | Self::Deflate(encoder) => encoder.finish(),
+            Self::Brotli(writer) => Ok(writer.into_inner()),
+            Self::Zstd(encoder, _dictionary) => encoder.finish(),
+            Self::Lz4(encoder) => Ok(encoder.finish()?),
+            Self::Snappy(encoder) => encoder.into_inner().map_err(|err| err.into_error()),
+            Self::ZstdSeekable(encoder) => encoder.finish(),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::PyErrArguments;
+use std::fmt::Display;
+use std::io;
+
+// Every fastcomp exception derives from ValueError, which is all earlier releases raised.
+pyo3::create_exception!(fastcomp, CompressionError, PyValueError);
+pyo3::create_exception!(fastcomp, TruncatedInputError, CompressionError);
+pyo3::create_exception!(fastcomp, CorruptDataError, CompressionError);
+pyo3::create_exception!(fastcomp, InvalidParameterError, CompressionError);
+pyo3::create_exception!(fastcomp, DecompressionLimitExceeded, CompressionError);
+pyo3::create_exception!(fastcomp, BufferTooSmall, CompressionError);
+
+#[derive(Clone, Copy)]
+enum ErrorKind {
+    Truncated,
+    Corrupt,
+    InvalidParameter,
+    Other,
+}
+
+/// A codec failure with the details callers use to tell failures apart. It becomes the
+/// exception matching its kind, with `codec`, `consumed` (input bytes read before the
+/// failure) and, for truncated input, `partial_output` set on the instance.
+pub(crate) struct CodecError {
+    kind: ErrorKind,
+    message: String,
+    codec: Option<&'static str>,
+    consumed: Option<usize>,
+    partial_output: Vec<u8>,
+}
+
+impl CodecError {
+    fn new(kind: ErrorKind, codec: Option<&'static str>, message: impl Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            codec,
+            consumed: None,
+            partial_output: Vec::new(),
+        }
+    }
+
+    /// The input ended before the stream did; `partial_output` is everything decoded.
+    pub fn truncated(codec: &'static str, consumed: usize, partial_output: Vec<u8>) -> Self {
+        Self {
+            consumed: Some(consumed),
+            partial_output,
+            ..Self::new(
+                ErrorKind::Truncated,
+                Some(codec),
+                format!("truncated {codec} input: stream incomplete after {consumed} bytes"),
+            )
+        }
+    }
+
+    pub fn corrupt(
+        codec: Option<&'static str>,
+        consumed: Option<usize>,
+        err: impl Display,
+    ) -> Self {
+        Self {
+            consumed,
+            ..Self::new(ErrorKind::Corrupt, codec, err)
+        }
+    }
+
+    pub fn invalid_parameter(codec: Option<&'static str>, message: impl Display) -> Self {
+        Self::new(ErrorKind::InvalidParameter, codec, message)
+    }
+
+    /// Classifies an error from a pull-style decoder: running out of input means the data
+    /// was truncated, anything else that it is corrupt.
+    pub fn decode(
+        codec: &'static str,
+        consumed: usize,
+        err: io::Error,
+        partial_output: Vec<u8>,
+    ) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::truncated(codec, consumed, partial_output),
+            _ => Self::corrupt(Some(codec), Some(consumed), err),
+        }
+    }
+
+    /// Classifies an error from an encoder or from building a codec's state, where
+    /// `InvalidInput` means a rejected setting.
+    pub fn encode(codec: Option<&'static str>, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidInput => Self::invalid_parameter(codec, err),
+            _ => Self::new(ErrorKind::Other, codec, err),
+        }
+    }
+}
+
+impl PyErrArguments for CodecError {
+    /// Builds the exception instance itself, which Python raises as is, so the attributes
+    /// are in place without holding the GIL when the error is created.
+    fn arguments(self, py: Python<'_>) -> PyObject {
+        let exception_type = match self.kind {
+            ErrorKind::Truncated => py.get_type::<TruncatedInputError>(),
+            ErrorKind::Corrupt => py.get_type::<CorruptDataError>(),
+            ErrorKind::InvalidParameter => py.get_type::<InvalidParameterError>(),
+            ErrorKind::Other => py.get_type::<CompressionError>(),
+        };
+        let build = || -> PyResult<PyObject> {
+            let exception = exception_type.call1((&self.message,))?;
+            exception.setattr("codec", self.codec)?;
+            exception.setattr("consumed", self.consumed)?;
+            if let ErrorKind::Truncated = self.kind {
+                exception.setattr("partial_output", PyBytes::new(py, &self.partial_output))?;
+            }
+            Ok(exception.into())
+        };
+        build().unwrap_or_else(|_| (self.message.clone(),).into_py(py))
+    }
+}
+
+impl From<CodecError> for PyErr {
+    fn from(err: CodecError) -> Self {
+        match err.kind {
+            ErrorKind::Truncated => PyErr::new::<TruncatedInputError, _>(err),
+            ErrorKind::Corrupt => PyErr::new::<CorruptDataError, _>(err),
+            ErrorKind::InvalidParameter => PyErr::new::<InvalidParameterError, _>(err),
+            ErrorKind::Other => PyErr::new::<CompressionError, _>(err),
+        }
+    }
+}
+
+/// Adds the exception types to the module, with class-level defaults so instances raised
+/// from Python code also have every attribute.
+pub(crate) fn register(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    let base = py.get_type::<CompressionError>();
+    base.setattr("codec", py.None())?;
+    base.setattr("consumed", py.None())?;
+    let truncated = py.get_type::<TruncatedInputError>();
+    truncated.setattr("partial_output", PyBytes::new(py, b""))?;
+    m.add("CompressionError", base)?;
+    m.add("TruncatedInputError", truncated)?;
+    m.add("CorruptDataError", py.get_type::<CorruptDataError>())?;
+    m.add(
+        "InvalidParameterError",
+        py.get_type::<InvalidParameterError>(),
+    )?;
+    m.add(
+        "DecompressionLimitExceeded",
+        py.get_type::<DecompressionLimitExceeded>(),
+    )?;
+    m.add("BufferTooSmall", py.get_type::<BufferTooSmall>())?;
+    Ok(())
+}
@@ -1,205 +1,1342 @@
+mod accept;
+mod adaptive;
+mod brotli_params;
+mod decode;
+mod encode;
+mod errors;
+mod output;
+mod permessage_deflate;
+mod pool;
+mod precompressed;
+mod responder;
+mod seekable;
+mod shared_dict;
+mod zip_stream;
+mod zstd_dict;
+mod zstd_frame;
+mod zstd_params;
+
+use brotli::enc::BrotliEncoderParams;
+use brotli_params::BrotliParams;
+use decode::{
+    BlockStreamDecoder, BrotliStreamDecoder, DeflateStreamDecoder, GzipStreamDecoder, Lz4Format,
+    SnappyFormat, StreamDecoder, ZstdStreamDecoder,
+};
+use encode::{FlushMode, GzipHeader, StreamEncoder};
+use errors::{BufferTooSmall, CodecError, DecompressionLimitExceeded, InvalidParameterError};
 use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use pyo3::exceptions::PyValueError;
+use output::{OutBuffer, Output, SliceOutput};
+use pyo3::exceptions::{PyEOFError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::{PyByteArray, PyBytes};
-use std::io::{Read, Write};
+use pyo3::types::{PyByteArray, PyBytes, PyMemoryView, PyString};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+use zstd_dict::ZstdDictionary;
+use zstd_params::ZstdParams;
+
+/// Module-wide decoded size limit; `usize::MAX` means unlimited.
+static DEFAULT_MAX_OUTPUT_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn resolve_max_output_size(max_output_size: Option<usize>) -> Option<usize> {
+    max_output_size.or(match DEFAULT_MAX_OUTPUT_SIZE.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        size => Some(size),
+    })
+}
+
+fn limit_exceeded(limit: usize) -> PyErr {
+    DecompressionLimitExceeded::new_err(format!(
+        "decompressed output exceeds max_output_size of {limit} bytes"
+    ))
+}
+
+/// Input for pull-style decoders that records how much of `data` they have read.
+struct CountingReader<'a> {
+    data: &'a [u8],
+    consumed: &'a Cell<usize>,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = (&self.data[self.consumed.get()..]).read(buf)?;
+        self.consumed.set(self.consumed.get() + read);
+        Ok(read)
+    }
+}
+
+/// Reads `reader` to the end, stopping one byte past `max_output_size` so the full output
+/// of a decompression bomb is never buffered. `consumed` is the counter of the
+/// `CountingReader` the decoder reads from.
+fn read_to_end_limited<R: Read>(
+    codec: &'static str,
+    reader: R,
+    consumed: &Cell<usize>,
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    let limit = max_output_size.map_or(usize::MAX, |limit| limit.saturating_add(1));
+    if let Err(err) = decode::read_into(reader, output, limit) {
+        let partial_output = output.kept().to_vec();
+        return Err(CodecError::decode(codec, consumed.get(), err, partial_output).into());
+    }
+    match max_output_size {
+        Some(limit) if output.len() > limit => Err(limit_exceeded(limit)),
+        _ => Ok(()),
+    }
+}
+
+/// Decodes all of `data` with push-style decoders, starting a fresh one for every further
+/// member or frame when `concatenated` is set. With `nul_padding`, NUL bytes between and
+/// after members are skipped. Input that ends mid-stream is reported as truncated along
+/// with everything decoded before it.
+fn decode_all<D: StreamDecoder>(
+    codec: &'static str,
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+    concatenated: bool,
+    nul_padding: bool,
+    mut new_decoder: impl FnMut() -> io::Result<D>,
+) -> PyResult<()> {
+    let limit = max_output_size.map_or(usize::MAX, |limit| limit.saturating_add(1));
+    let mut consumed = 0;
+    loop {
+        let mut decoder = new_decoder().map_err(|err| CodecError::encode(Some(codec), err))?;
+        let progress = decode::drive(&mut decoder, &data[consumed..], output, limit);
+        consumed += progress.consumed;
+        if let Some(err) = progress.error {
+            return Err(CodecError::corrupt(Some(codec), Some(consumed), err).into());
+        }
+        if let Some(limit) = max_output_size.filter(|limit| output.len() > *limit) {
+            return Err(limit_exceeded(limit));
+        }
+        if !progress.finished {
+            let partial_output = output.kept().to_vec();
+            return Err(CodecError::truncated(codec, consumed, partial_output).into());
+        }
+        if nul_padding {
+            consumed += data[consumed..]
+                .iter()
+                .take_while(|byte| **byte == 0)
+                .count();
+        }
+        if !concatenated || consumed == data.len() || progress.consumed == 0 {
+            return Ok(());
+        }
+    }
+}
 
-fn to_bytes<'py>(obj: &'py PyAny) -> PyResult<&'py [u8]> {
+/// Reads a bytes-like argument. `bytes`, also behind a memoryview covering all of it, is
+/// borrowed; other buffers are copied, as they may change while the GIL is released.
+fn to_bytes(obj: &PyAny) -> PyResult<Cow<'_, [u8]>> {
     if let Ok(bytes) = obj.downcast::<PyBytes>() {
-        return Ok(bytes.as_bytes());
+        return Ok(Cow::Borrowed(bytes.as_bytes()));
     }
     if let Ok(bytearray) = obj.downcast::<PyByteArray>() {
-        unsafe {
-            return Ok(bytearray.as_bytes());
+        return Ok(Cow::Owned(bytearray.to_vec()));
+    }
+    let view =
+        PyMemoryView::from(obj).map_err(|_| PyValueError::new_err("expected bytes-like object"))?;
+    if let Ok(bytes) = view.getattr("obj")?.downcast::<PyBytes>() {
+        let nbytes: usize = view.getattr("nbytes")?.extract()?;
+        if nbytes == bytes.as_bytes().len() && view.getattr("c_contiguous")?.is_true()? {
+            return Ok(Cow::Borrowed(bytes.as_bytes()));
+        }
+    }
+    let bytes = view.call_method0("tobytes")?.downcast::<PyBytes>()?;
+    Ok(Cow::Borrowed(bytes.as_bytes()))
+}
+
+/// Reads a header value given as `str` or as raw bytes.
+fn text_or_bytes(obj: &PyAny) -> PyResult<Vec<u8>> {
+    match obj.downcast::<PyString>() {
+        Ok(text) => Ok(text.to_str()?.as_bytes().to_vec()),
+        Err(_) => Ok(to_bytes(obj)?.into_owned()),
+    }
+}
+
+/// Rejects a compression level outside the range `codec` accepts, before an encoder is
+/// built; zlib asserts on levels above 9, while brotli and zstd silently clamp.
+fn check_level(codec: &'static str, level: impl Into<i64>) -> Result<(), CodecError> {
+    let level = level.into();
+    let (min, max) = match codec {
+        "gzip" | "deflate" => (0, 9),
+        "br" => (0, 11),
+        "zstd" => {
+            let range = zstd::compression_level_range();
+            (i64::from(*range.start()), i64::from(*range.end()))
         }
+        _ => return Ok(()),
+    };
+    if (min..=max).contains(&level) {
+        return Ok(());
     }
-    Err(PyValueError::new_err("expected bytes-like object"))
+    Err(CodecError::invalid_parameter(
+        Some(codec),
+        format!("{codec} level must be between {min} and {max}, got {level}"),
+    ))
+}
+
+fn already_finished() -> PyErr {
+    PyValueError::new_err("compressor already finished")
+}
+
+/// Encodes a gzip header text field as ISO 8859-1, which is all the format allows.
+fn latin1_field(name: &str, value: Option<&str>) -> PyResult<Option<Vec<u8>>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    value
+        .chars()
+        .map(|ch| match u8::try_from(u32::from(ch)) {
+            Ok(byte) if byte != 0 => Ok(byte),
+            _ => Err(CodecError::invalid_parameter(
+                Some("gzip"),
+                format!("gzip {name} must be non-NUL ISO 8859-1 text"),
+            )
+            .into()),
+        })
+        .collect::<PyResult<Vec<u8>>>()
+        .map(Some)
 }
 
-fn gzip_compress_impl(data: &[u8], level: u32) -> PyResult<Vec<u8>> {
-    let compression = Compression::new(level as u32);
-    let mut encoder = GzEncoder::new(Vec::new(), compression);
+fn gzip_header(
+    filename: Option<&str>,
+    mtime: Option<u32>,
+    comment: Option<&str>,
+) -> PyResult<GzipHeader> {
+    Ok(GzipHeader {
+        filename: latin1_field("filename", filename)?,
+        comment: latin1_field("comment", comment)?,
+        mtime: mtime.unwrap_or(0),
+    })
+}
+
+fn gzip_compress_impl(
+    data: &[u8],
+    output: &mut impl Write,
+    level: u32,
+    header: &GzipHeader,
+) -> PyResult<()> {
+    encode::FlateEncoder::gzip(level, header)
+        .compress_to(data, output)
+        .map_err(|err| CodecError::encode(Some("gzip"), err))?;
+    Ok(())
+}
+
+/// Decodes every concatenated gzip member unless `multi_member` is false, in which case
+/// anything after the first member is ignored. NUL padding after a member is skipped, as
+/// CPython's `gzip.decompress` does.
+fn gzip_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+    multi_member: bool,
+) -> PyResult<()> {
+    decode_all(
+        "gzip",
+        data,
+        output,
+        max_output_size,
+        multi_member,
+        true,
+        || Ok(GzipStreamDecoder::new()),
+    )
+}
+
+fn deflate_compress_impl(
+    data: &[u8],
+    output: &mut impl Write,
+    level: u32,
+    raw: bool,
+) -> PyResult<()> {
+    encode::FlateEncoder::deflate(level, raw)
+        .compress_to(data, output)
+        .map_err(|err| CodecError::encode(Some("deflate"), err))?;
+    Ok(())
+}
+
+fn deflate_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    decode_all(
+        "deflate",
+        data,
+        output,
+        max_output_size,
+        false,
+        false,
+        || Ok(DeflateStreamDecoder::new()),
+    )
+}
+
+fn brotli_compress_impl(
+    data: &[u8],
+    output: &mut impl Write,
+    params: &BrotliEncoderParams,
+) -> PyResult<()> {
+    let mut writer = brotli::CompressorWriter::with_params(output, 4096, params);
+    writer
+        .write_all(data)
+        .map_err(|err| CodecError::encode(Some("br"), err))?;
+    Ok(())
+}
+
+/// Decodes a brotli stream, optionally compressed against a custom dictionary.
+fn brotli_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+    dictionary: Option<&[u8]>,
+) -> PyResult<()> {
+    decode_all("br", data, output, max_output_size, false, false, || {
+        Ok(match dictionary {
+            Some(dictionary) => BrotliStreamDecoder::with_dictionary(dictionary.to_vec()),
+            None => BrotliStreamDecoder::new(),
+        })
+    })
+}
+
+fn zstd_compress_impl(
+    data: &[u8],
+    output: &mut impl Write,
+    level: i32,
+    dictionary: Option<&EncoderDictionary<'_>>,
+    params: &ZstdParams,
+) -> PyResult<()> {
+    let encode_error = |err| CodecError::encode(Some("zstd"), err);
+    let mut encoder = match dictionary {
+        Some(dictionary) => {
+            zstd::stream::write::Encoder::with_prepared_dictionary(output, dictionary)
+        }
+        None => zstd::stream::write::Encoder::new(output, level),
+    }
+    .map_err(encode_error)?;
+    params
+        .apply(&mut encoder, Some(data.len()))
+        .map_err(encode_error)?;
+    encoder.write_all(data).map_err(encode_error)?;
+    encoder.finish().map_err(encode_error)?;
+    Ok(())
+}
+
+/// Decodes every concatenated zstd frame, skipping skippable frames.
+fn zstd_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+    dictionary: Option<Arc<DecoderDictionary<'static>>>,
+) -> PyResult<()> {
+    decode_all("zstd", data, output, max_output_size, true, false, || {
+        ZstdStreamDecoder::new(dictionary.clone())
+    })
+}
+
+fn lz4_compress_impl(data: &[u8], output: &mut impl Write) -> PyResult<()> {
+    let info = lz4_flex::frame::FrameInfo::new()
+        .content_size(Some(data.len() as u64))
+        .content_checksum(true);
+    let mut encoder = lz4_flex::frame::FrameEncoder::with_frame_info(info, output);
     encoder
         .write_all(data)
-        .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        .map_err(|err| CodecError::encode(Some("lz4"), err))?;
     encoder
         .finish()
-        .map_err(|err| PyValueError::new_err(err.to_string()))
+        .map_err(|err| CodecError::encode(Some("lz4"), err.into()))?;
+    Ok(())
 }
 
-fn gzip_decompress_impl(data: &[u8]) -> PyResult<Vec<u8>> {
-    let mut decoder = GzDecoder::new(data);
-    let mut out = Vec::new();
-    decoder
-        .read_to_end(&mut out)
-        .map_err(|err| PyValueError::new_err(err.to_string()))?;
-    Ok(out)
+/// lz4_flex rejects skippable frames, so any in front of the first frame are stepped over
+/// here, as `Lz4Decompressor` does.
+fn lz4_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    let mut start = 0;
+    while let Some(len) = data
+        .get(start..start + 8)
+        .and_then(decode::lz4_skippable_frame)
+    {
+        start += 8 + len;
+    }
+    if start > data.len() {
+        return Err(CodecError::truncated("lz4", data.len(), Vec::new()).into());
+    }
+    let consumed = Cell::new(start);
+    let input = CountingReader {
+        data,
+        consumed: &consumed,
+    };
+    let decoder = lz4_flex::frame::FrameDecoder::new(input);
+    read_to_end_limited("lz4", decoder, &consumed, output, max_output_size)
 }
 
-fn brotli_compress_impl(data: &[u8], level: u32) -> PyResult<Vec<u8>> {
-    let mut out = Vec::new();
-    {
-        let mut writer = brotli::CompressorWriter::new(&mut out, 4096, level, 22);
-        writer
-            .write_all(data)
-            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+/// Writes the Snappy framing format.
+fn snappy_compress_impl(data: &[u8], output: &mut impl Write) -> PyResult<()> {
+    let mut encoder = snap::write::FrameEncoder::new(output);
+    encoder
+        .write_all(data)
+        .map_err(|err| CodecError::encode(Some("snappy"), err))?;
+    encoder
+        .into_inner()
+        .map_err(|err| CodecError::encode(Some("snappy"), err.into_error()))?;
+    Ok(())
+}
+
+/// Compresses `data` into a bare Snappy block.
+fn snappy_raw_compress_impl(data: &[u8]) -> PyResult<Vec<u8>> {
+    Ok(snap::raw::Encoder::new()
+        .compress_vec(data)
+        .map_err(|err| CodecError::encode(Some("snappy"), err.into()))?)
+}
+
+fn snappy_decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    let consumed = Cell::new(0);
+    let input = CountingReader {
+        data,
+        consumed: &consumed,
+    };
+    let decoder = snap::read::FrameDecoder::new(input);
+    read_to_end_limited("snappy", decoder, &consumed, output, max_output_size)
+}
+
+/// Raw Snappy blocks declare their decoded length up front, so oversized output is
+/// rejected before anything is allocated. A block is decoded as a whole, so its errors
+/// cannot tell truncation from corruption.
+fn snappy_raw_decompress_impl(data: &[u8], max_output_size: Option<usize>) -> PyResult<Vec<u8>> {
+    let corrupt = |err: snap::Error| CodecError::corrupt(Some("snappy"), None, err);
+    let len = snap::raw::decompress_len(data).map_err(corrupt)?;
+    if let Some(limit) = max_output_size.filter(|limit| len > *limit) {
+        return Err(limit_exceeded(limit));
+    }
+    Ok(snap::raw::Decoder::new()
+        .decompress_vec(data)
+        .map_err(corrupt)?)
+}
+
+fn unsupported_coding(coding: &str) -> PyErr {
+    InvalidParameterError::new_err(format!("unsupported content coding: {coding}"))
+}
+
+/// Raises the errors `compress_coding_impl` would for `coding` and `level`, without
+/// compressing anything.
+fn check_coding(coding: &str, level: Option<i32>) -> PyResult<()> {
+    let codec = match coding.to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => "gzip",
+        "deflate" => "deflate",
+        "br" => "br",
+        "zstd" => "zstd",
+        "lz4" | "snappy" => return Ok(()),
+        _ => return Err(unsupported_coding(coding)),
+    };
+    if let Some(level) = level {
+        check_level(codec, level)?;
+    }
+    Ok(())
+}
+
+/// Applies a single content coding, using each codec's default level when none is given.
+/// `lz4` and `snappy` (framed) have no levels and ignore `level`.
+fn compress_coding_impl(
+    coding: &str,
+    data: &[u8],
+    output: &mut impl Write,
+    level: Option<i32>,
+) -> PyResult<()> {
+    let level = |codec, default| {
+        let level = level.unwrap_or(default);
+        check_level(codec, level).map(|()| level)
+    };
+    match coding.to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => {
+            let header = GzipHeader::default();
+            gzip_compress_impl(data, output, level("gzip", 5)? as u32, &header)
+        }
+        "deflate" => deflate_compress_impl(data, output, level("deflate", 5)? as u32, false),
+        "br" => {
+            let params = BrotliParams::default().encoder_params(level("br", 5)? as u32);
+            brotli_compress_impl(data, output, &params)
+        }
+        "zstd" => {
+            let params = ZstdParams::default();
+            zstd_compress_impl(data, output, level("zstd", 3)?, None, &params)
+        }
+        "lz4" => lz4_compress_impl(data, output),
+        "snappy" => snappy_compress_impl(data, output),
+        _ => Err(unsupported_coding(coding)),
     }
-    Ok(out)
 }
 
-fn brotli_decompress_impl(data: &[u8]) -> PyResult<Vec<u8>> {
-    let mut out = Vec::new();
-    brotli::Decompressor::new(data, 4096)
-        .read_to_end(&mut out)
-        .map_err(|err| PyValueError::new_err(err.to_string()))?;
-    Ok(out)
+/// Raises `BufferTooSmall` unless `required` bytes fit in an output buffer of `available`.
+fn check_fits(available: usize, required: usize) -> PyResult<usize> {
+    if required > available {
+        return Err(BufferTooSmall::new_err((
+            format!("output buffer holds {available} bytes but {required} are required"),
+            required,
+        )));
+    }
+    Ok(required)
 }
 
-fn zstd_compress_impl(data: &[u8], level: i32) -> PyResult<Vec<u8>> {
-    zstd::stream::encode_all(data, level).map_err(|err| PyValueError::new_err(err.to_string()))
+/// Undoes a single content coding.
+fn decompress_coding_impl(
+    coding: &str,
+    data: &[u8],
+    output: &mut impl Output,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    match coding.to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => gzip_decompress_impl(data, output, max_output_size, true),
+        "deflate" => deflate_decompress_impl(data, output, max_output_size),
+        "br" => brotli_decompress_impl(data, output, max_output_size, None),
+        "zstd" => zstd_decompress_impl(data, output, max_output_size, None),
+        "lz4" => lz4_decompress_impl(data, output, max_output_size),
+        "snappy" => snappy_decompress_impl(data, output, max_output_size),
+        _ => Err(unsupported_coding(coding)),
+    }
+}
+
+/// Decodes `data` according to a `Content-Encoding` value, undoing the listed codings in
+/// reverse order, or by sniffing magic bytes when no encoding is given. Only the last
+/// coding undone writes to `output`; the ones before it decode into temporary buffers.
+fn decompress_impl(
+    data: &[u8],
+    output: &mut impl Output,
+    encoding: Option<&str>,
+    max_output_size: Option<usize>,
+) -> PyResult<()> {
+    let Some(encoding) = encoding else {
+        let coding = decode::detect_coding(data).ok_or_else(|| {
+            CodecError::corrupt(None, None, "unable to detect compression format")
+        })?;
+        return decompress_coding_impl(coding, data, output, max_output_size);
+    };
+    let codings: Vec<&str> = encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+        .collect();
+    let Some((first, rest)) = codings.split_first() else {
+        if let Some(limit) = max_output_size.filter(|limit| data.len() > *limit) {
+            return Err(limit_exceeded(limit));
+        }
+        output.write_all(data)?;
+        return Ok(());
+    };
+    let mut input = Cow::Borrowed(data);
+    for coding in rest.iter().rev() {
+        let mut decoded = Vec::new();
+        decompress_coding_impl(coding, &input, &mut decoded, max_output_size)?;
+        input = Cow::Owned(decoded);
+    }
+    decompress_coding_impl(first, &input, output, max_output_size)
 }
 
-fn zstd_decompress_impl(data: &[u8]) -> PyResult<Vec<u8>> {
-    zstd::stream::decode_all(data).map_err(|err| PyValueError::new_err(err.to_string()))
+#[pyfunction]
+#[pyo3(
+    signature = (data, level=None, *, filename=None, mtime=None, comment=None),
+    text_signature = "(data, level=5, *, filename=None, mtime=None, comment=None)"
+)]
+fn gzip_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    level: Option<u32>,
+    filename: Option<&str>,
+    mtime: Option<u32>,
+    comment: Option<&str>,
+) -> PyResult<Py<PyBytes>> {
+    let level = level.unwrap_or(5);
+    check_level("gzip", level)?;
+    let data = to_bytes(data)?;
+    let header = gzip_header(filename, mtime, comment)?;
+    let mut output = Vec::new();
+    py.allow_threads(|| gzip_compress_impl(&data, &mut output, level, &header))?;
+    Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-#[pyo3(text_signature = "(data, level=5)")]
-fn gzip_compress(py: Python<'_>, data: &PyAny, level: Option<u32>) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (data, *, max_output_size=None, multi_member=true))]
+fn gzip_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+    multi_member: bool,
+) -> PyResult<Py<PyBytes>> {
     let data = to_bytes(data)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut output = Vec::new();
+    py.allow_threads(|| gzip_decompress_impl(&data, &mut output, max_output_size, multi_member))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+/// Header metadata of the first gzip member.
+#[pyclass(module = "fastcomp")]
+struct GzipInfo {
+    #[pyo3(get)]
+    filename: Option<String>,
+    #[pyo3(get)]
+    mtime: u32,
+    #[pyo3(get)]
+    os: u8,
+    #[pyo3(get)]
+    comment: Option<String>,
+}
+
+#[pyfunction]
+fn gzip_info(data: &PyAny) -> PyResult<GzipInfo> {
+    let data = to_bytes(data)?;
+    match decode::gzip_header_len(&data) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(CodecError::truncated("gzip", data.len(), Vec::new()).into()),
+        Err(err) => return Err(CodecError::corrupt(Some("gzip"), None, err).into()),
+    }
+    let decoder = GzDecoder::new(&*data);
+    let header = decoder
+        .header()
+        .ok_or_else(|| CodecError::corrupt(Some("gzip"), None, "invalid gzip header"))?;
+    let latin1 = |field: &[u8]| field.iter().map(|byte| char::from(*byte)).collect();
+    Ok(GzipInfo {
+        filename: header.filename().map(latin1),
+        mtime: header.mtime(),
+        os: header.operating_system(),
+        comment: header.comment().map(latin1),
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, level=5, *, raw=false))]
+fn deflate_compress(py: Python<'_>, data: &PyAny, level: u32, raw: bool) -> PyResult<Py<PyBytes>> {
+    check_level("deflate", level)?;
+    let data = to_bytes(data)?;
+    let mut output = Vec::new();
+    py.allow_threads(|| deflate_compress_impl(&data, &mut output, level, raw))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, *, max_output_size=None))]
+fn deflate_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut output = Vec::new();
+    py.allow_threads(|| deflate_decompress_impl(&data, &mut output, max_output_size))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, level=None, *, params=None))]
+fn brotli_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    level: Option<u32>,
+    params: Option<BrotliParams>,
+) -> PyResult<Py<PyBytes>> {
     let level = level.unwrap_or(5);
-    let output = py.allow_threads(|| gzip_compress_impl(data, level))?;
+    check_level("br", level)?;
+    let data = to_bytes(data)?;
+    let params = params.unwrap_or_default().encoder_params(level);
+    let mut output = Vec::new();
+    py.allow_threads(|| brotli_compress_impl(&data, &mut output, &params))?;
     Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-fn gzip_decompress(py: Python<'_>, data: &PyAny) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (data, *, max_output_size=None))]
+fn brotli_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
     let data = to_bytes(data)?;
-    let output = py.allow_threads(|| gzip_decompress_impl(data))?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut output = Vec::new();
+    py.allow_threads(|| brotli_decompress_impl(&data, &mut output, max_output_size, None))?;
     Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-fn brotli_compress(py: Python<'_>, data: &PyAny, level: Option<u32>) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (data, level=None, *, dictionary=None, params=None))]
+fn zstd_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    level: Option<i32>,
+    dictionary: Option<PyRef<'_, ZstdDictionary>>,
+    params: Option<ZstdParams>,
+) -> PyResult<Py<PyBytes>> {
+    let level = level.unwrap_or(3);
+    check_level("zstd", level)?;
     let data = to_bytes(data)?;
-    let output = py.allow_threads(|| brotli_compress_impl(data, level.unwrap_or(5)))?;
+    let dictionary = dictionary.map(|dictionary| dictionary.encoder(level));
+    let params = params.unwrap_or_default();
+    let mut output = Vec::new();
+    py.allow_threads(|| {
+        zstd_compress_impl(&data, &mut output, level, dictionary.as_deref(), &params)
+    })?;
     Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-fn brotli_decompress(py: Python<'_>, data: &PyAny) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (data, *, max_output_size=None, dictionary=None))]
+fn zstd_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+    dictionary: Option<PyRef<'_, ZstdDictionary>>,
+) -> PyResult<Py<PyBytes>> {
     let data = to_bytes(data)?;
-    let output = py.allow_threads(|| brotli_decompress_impl(data))?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let dictionary = dictionary.map(|dictionary| dictionary.decoder());
+    let mut output = Vec::new();
+    py.allow_threads(|| zstd_decompress_impl(&data, &mut output, max_output_size, dictionary))?;
     Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-fn zstd_compress(py: Python<'_>, data: &PyAny, level: Option<i32>) -> PyResult<Py<PyBytes>> {
+fn lz4_compress(py: Python<'_>, data: &PyAny) -> PyResult<Py<PyBytes>> {
     let data = to_bytes(data)?;
-    let output = py.allow_threads(|| zstd_compress_impl(data, level.unwrap_or(3)))?;
+    let mut output = Vec::new();
+    py.allow_threads(|| lz4_compress_impl(&data, &mut output))?;
     Ok(PyBytes::new(py, &output).into())
 }
 
 #[pyfunction]
-fn zstd_decompress(py: Python<'_>, data: &PyAny) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (data, *, max_output_size=None))]
+fn lz4_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
     let data = to_bytes(data)?;
-    let output = py.allow_threads(|| zstd_decompress_impl(data))?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut output = Vec::new();
+    py.allow_threads(|| lz4_decompress_impl(&data, &mut output, max_output_size))?;
     Ok(PyBytes::new(py, &output).into())
 }
 
-#[pyclass(module = "fastcomp")]
-struct GzipCompressor {
-    level: u32,
+#[pyfunction]
+#[pyo3(signature = (data, *, framed=true))]
+fn snappy_compress(py: Python<'_>, data: &PyAny, framed: bool) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let output = py.allow_threads(|| {
+        if !framed {
+            return snappy_raw_compress_impl(&data);
+        }
+        let mut output = Vec::new();
+        snappy_compress_impl(&data, &mut output).map(|()| output)
+    })?;
+    Ok(PyBytes::new(py, &output).into())
 }
 
-#[pymethods]
-impl GzipCompressor {
-    #[new]
-    fn new(level: Option<u32>) -> PyResult<Self> {
-        Ok(Self {
-            level: level.unwrap_or(5),
-        })
+#[pyfunction]
+#[pyo3(signature = (data, *, framed=true, max_output_size=None))]
+fn snappy_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    framed: bool,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let output = py.allow_threads(|| {
+        if !framed {
+            return snappy_raw_decompress_impl(&data, max_output_size);
+        }
+        let mut output = Vec::new();
+        snappy_decompress_impl(&data, &mut output, max_output_size).map(|()| output)
+    })?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, encoding=None, *, max_output_size=None))]
+fn decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    encoding: Option<&str>,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut output = Vec::new();
+    py.allow_threads(|| decompress_impl(&data, &mut output, encoding, max_output_size))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+/// Compresses straight into `out` and returns the number of bytes written. When `out` is
+/// too small, `BufferTooSmall` reports the size needed and `out` holds a partial result.
+#[pyfunction]
+#[pyo3(signature = (codec, data, out, level=None))]
+fn compress_into(
+    py: Python<'_>,
+    codec: &str,
+    data: &PyAny,
+    out: &PyAny,
+    level: Option<i32>,
+) -> PyResult<usize> {
+    let data = to_bytes(data)?;
+    let mut out = OutBuffer::new(out)?;
+    let buf = out.as_mut_slice();
+    let available = buf.len();
+    let required = py.allow_threads(|| {
+        let mut output = SliceOutput::new(buf);
+        compress_coding_impl(codec, &data, &mut output, level).map(|()| output.len())
+    })?;
+    check_fits(available, required)
+}
+
+/// Decompresses straight into `out`, with the same contract as `compress_into`.
+#[pyfunction]
+#[pyo3(signature = (codec, data, out, *, max_output_size=None))]
+fn decompress_into(
+    py: Python<'_>,
+    codec: Option<&str>,
+    data: &PyAny,
+    out: &PyAny,
+    max_output_size: Option<usize>,
+) -> PyResult<usize> {
+    let data = to_bytes(data)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let mut out = OutBuffer::new(out)?;
+    let buf = out.as_mut_slice();
+    let available = buf.len();
+    let required = py.allow_threads(|| {
+        let mut output = SliceOutput::new(buf);
+        decompress_impl(&data, &mut output, codec, max_output_size).map(|()| output.len())
+    })?;
+    check_fits(available, required)
+}
+
+/// Compresses with one content coding on the worker pool; the returned future resolves to
+/// the compressed bytes.
+#[pyfunction]
+#[pyo3(signature = (codec, data, level=None))]
+fn acompress(
+    py: Python<'_>,
+    codec: String,
+    data: &PyAny,
+    level: Option<i32>,
+) -> PyResult<PyObject> {
+    let data = to_bytes(data)?.into_owned();
+    pool::spawn(py, move || {
+        let mut output = Vec::new();
+        compress_coding_impl(&codec, &data, &mut output, level).map(|()| output)
+    })
+}
+
+/// Asynchronous `decompress`, run on the worker pool.
+#[pyfunction]
+#[pyo3(signature = (codec, data, *, max_output_size=None))]
+fn adecompress(
+    py: Python<'_>,
+    codec: Option<String>,
+    data: &PyAny,
+    max_output_size: Option<usize>,
+) -> PyResult<PyObject> {
+    let data = to_bytes(data)?.into_owned();
+    let max_output_size = resolve_max_output_size(max_output_size);
+    pool::spawn(py, move || {
+        let mut output = Vec::new();
+        decompress_impl(&data, &mut output, codec.as_deref(), max_output_size).map(|()| output)
+    })
+}
+
+#[pyfunction]
+fn set_default_max_output_size(max_output_size: Option<usize>) {
+    DEFAULT_MAX_OUTPUT_SIZE.store(max_output_size.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+#[pyfunction]
+fn get_default_max_output_size() -> Option<usize> {
+    resolve_max_output_size(None)
+}
+
+fn available_codings(available: &Option<Vec<String>>) -> Vec<&str> {
+    match available {
+        Some(available) => available.iter().map(String::as_str).collect(),
+        None => accept::DEFAULT_PREFERENCE.to_vec(),
+    }
+}
+
+fn compressor_for(py: Python<'_>, coding: &str) -> PyResult<PyObject> {
+    match coding.to_ascii_lowercase().as_str() {
+        "gzip" => Ok(Py::new(py, GzipCompressor::new(None, None, None, None)?)?.into_py(py)),
+        "deflate" => Ok(Py::new(py, DeflateCompressor::new(5, false)?)?.into_py(py)),
+        "br" => Ok(Py::new(py, BrotliCompressor::new(None, None)?)?.into_py(py)),
+        "zstd" => Ok(Py::new(py, ZstdCompressor::new(None, None, None)?)?.into_py(py)),
+        _ => Err(unsupported_coding(coding)),
     }
+}
+
+#[pyfunction]
+#[pyo3(signature = (accept_encoding, available=None))]
+fn negotiate(accept_encoding: &PyAny, available: Option<Vec<String>>) -> PyResult<Option<String>> {
+    let header = to_bytes(accept_encoding)?;
+    let header = String::from_utf8_lossy(&header);
+    let available = available_codings(&available);
+    Ok(accept::negotiate(&header, &available).map(str::to_string))
+}
+
+#[pyfunction]
+#[pyo3(signature = (accept_encoding, available=None))]
+fn negotiate_compressor(
+    py: Python<'_>,
+    accept_encoding: &PyAny,
+    available: Option<Vec<String>>,
+) -> PyResult<Option<(String, PyObject)>> {
+    let header = to_bytes(accept_encoding)?;
+    let header = String::from_utf8_lossy(&header);
+    let available = available_codings(&available);
+    for coding in &available {
+        if !coding.eq_ignore_ascii_case("identity") && !accept::is_compressor_coding(coding) {
+            return Err(InvalidParameterError::new_err(format!(
+                "no streaming compressor for content coding {coding:?}; available codings \
+                 must be gzip, deflate, br, zstd or identity"
+            )));
+        }
+    }
+    let Some(chosen) = accept::negotiate(&header, &available) else {
+        return Ok(None);
+    };
+    Ok(Some((chosen.to_string(), compressor_for(py, chosen)?)))
+}
 
+/// Shared state behind the streaming compressor classes.
+#[pyclass(module = "fastcomp", subclass)]
+struct Compressor {
+    encoder: Option<StreamEncoder>,
+}
+
+impl Compressor {
+    fn new(encoder: StreamEncoder) -> Self {
+        Self {
+            encoder: Some(encoder),
+        }
+    }
+}
+
+#[pymethods]
+impl Compressor {
     fn update<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
         let data = to_bytes(data)?;
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(self.level));
-        encoder
-            .write_all(data)
-            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let encoder = self.encoder.as_mut().ok_or_else(already_finished)?;
         let chunk = encoder
-            .finish()
-            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            .update(&data)
+            .map_err(|err| CodecError::encode(Some(encoder.coding()), err))?;
+        Ok(PyBytes::new(py, &chunk))
+    }
+
+    /// Returns the bytes needed to decode everything passed to `update` so far, without
+    /// ending the stream.
+    #[pyo3(signature = (mode="sync"))]
+    fn flush<'py>(&mut self, py: Python<'py>, mode: &str) -> PyResult<&'py PyBytes> {
+        let mode = FlushMode::parse(mode).map_err(|err| CodecError::encode(None, err))?;
+        let encoder = self.encoder.as_mut().ok_or_else(already_finished)?;
+        let chunk = encoder
+            .flush(mode)
+            .map_err(|err| CodecError::encode(Some(encoder.coding()), err))?;
         Ok(PyBytes::new(py, &chunk))
     }
 
     fn finish<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
-        Ok(PyBytes::new(py, &[]))
+        let encoder = self.encoder.take().ok_or_else(already_finished)?;
+        let coding = encoder.coding();
+        let tail = encoder
+            .finish()
+            .map_err(|err| CodecError::encode(Some(coding), err))?;
+        Ok(PyBytes::new(py, &tail))
     }
 }
 
-#[pyclass(module = "fastcomp")]
-struct BrotliCompressor {
-    level: u32,
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct GzipCompressor;
+
+#[pymethods]
+impl GzipCompressor {
+    #[new]
+    #[pyo3(signature = (level=None, *, filename=None, mtime=None, comment=None))]
+    fn new(
+        level: Option<u32>,
+        filename: Option<&str>,
+        mtime: Option<u32>,
+        comment: Option<&str>,
+    ) -> PyResult<(Self, Compressor)> {
+        let level = level.unwrap_or(5);
+        check_level("gzip", level)?;
+        let header = gzip_header(filename, mtime, comment)?;
+        let encoder = StreamEncoder::gzip_with_header(level, &header);
+        Ok((Self, Compressor::new(encoder)))
+    }
 }
 
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct DeflateCompressor;
+
+#[pymethods]
+impl DeflateCompressor {
+    #[new]
+    #[pyo3(signature = (level=5, *, raw=false))]
+    fn new(level: u32, raw: bool) -> PyResult<(Self, Compressor)> {
+        check_level("deflate", level)?;
+        let encoder = StreamEncoder::deflate(level, raw);
+        Ok((Self, Compressor::new(encoder)))
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct BrotliCompressor;
+
 #[pymethods]
 impl BrotliCompressor {
     #[new]
-    fn new(level: Option<u32>) -> PyResult<Self> {
-        Ok(Self {
-            level: level.unwrap_or(5),
-        })
+    #[pyo3(signature = (level=None, *, params=None))]
+    fn new(level: Option<u32>, params: Option<BrotliParams>) -> PyResult<(Self, Compressor)> {
+        let level = level.unwrap_or(5);
+        check_level("br", level)?;
+        let params = params.unwrap_or_default().encoder_params(level);
+        let encoder = StreamEncoder::brotli(&params);
+        Ok((Self, Compressor::new(encoder)))
     }
+}
 
-    fn update<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
-        let data = to_bytes(data)?;
-        let output = brotli_compress_impl(data, self.level)?;
-        Ok(PyBytes::new(py, &output))
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct ZstdCompressor;
+
+#[pymethods]
+impl ZstdCompressor {
+    #[new]
+    #[pyo3(signature = (level=None, *, dictionary=None, params=None))]
+    fn new(
+        level: Option<i32>,
+        dictionary: Option<PyRef<'_, ZstdDictionary>>,
+        params: Option<ZstdParams>,
+    ) -> PyResult<(Self, Compressor)> {
+        let level = level.unwrap_or(3);
+        check_level("zstd", level)?;
+        let params = params.unwrap_or_default();
+        let encoder = match dictionary {
+            Some(dictionary) => {
+                StreamEncoder::zstd_with_dictionary(dictionary.encoder(level), &params)
+            }
+            None => StreamEncoder::zstd(level, &params),
+        }
+        .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+        Ok((Self, Compressor::new(encoder)))
     }
+}
 
-    fn finish<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
-        Ok(PyBytes::new(py, &[]))
+/// Writes the zstd seekable format, which `SeekableZstdReader` can read ranges from.
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct ZstdSeekableCompressor;
+
+#[pymethods]
+impl ZstdSeekableCompressor {
+    #[new]
+    #[pyo3(signature = (level=None, *, frame_size=seekable::DEFAULT_FRAME_SIZE, checksum=true))]
+    fn new(level: Option<i32>, frame_size: usize, checksum: bool) -> PyResult<(Self, Compressor)> {
+        let level = level.unwrap_or(3);
+        check_level("zstd", level)?;
+        let encoder = StreamEncoder::zstd_seekable(level, frame_size, checksum)
+            .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+        Ok((Self, Compressor::new(encoder)))
     }
 }
 
-#[pyclass(module = "fastcomp")]
-struct ZstdCompressor {
-    level: i32,
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct Lz4Compressor;
+
+#[pymethods]
+impl Lz4Compressor {
+    #[new]
+    fn new() -> (Self, Compressor) {
+        (Self, Compressor::new(StreamEncoder::lz4()))
+    }
 }
 
+/// Streams the Snappy framing format; raw Snappy blocks cannot be written incrementally.
+#[pyclass(module = "fastcomp", extends = Compressor)]
+struct SnappyCompressor;
+
 #[pymethods]
-impl ZstdCompressor {
+impl SnappyCompressor {
     #[new]
-    fn new(level: Option<i32>) -> PyResult<Self> {
-        Ok(Self {
-            level: level.unwrap_or(3),
-        })
+    fn new() -> (Self, Compressor) {
+        (Self, Compressor::new(StreamEncoder::snappy()))
     }
+}
 
-    fn update<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
+/// Shared state behind the streaming decompressor classes, modelled on the stdlib
+/// `bz2.BZ2Decompressor` and `lzma.LZMADecompressor` interface.
+#[pyclass(module = "fastcomp", subclass)]
+struct Decompressor {
+    codec: &'static str,
+    decoder: Box<dyn StreamDecoder>,
+    max_output_size: Option<usize>,
+    total_in: usize,
+    total_out: usize,
+    pending: Vec<u8>,
+    eof: bool,
+    unused_data: Vec<u8>,
+    needs_input: bool,
+}
+
+impl Decompressor {
+    fn new(
+        codec: &'static str,
+        decoder: Box<dyn StreamDecoder>,
+        max_output_size: Option<usize>,
+    ) -> Self {
+        Self {
+            codec,
+            decoder,
+            max_output_size: resolve_max_output_size(max_output_size),
+            total_in: 0,
+            total_out: 0,
+            pending: Vec::new(),
+            eof: false,
+            unused_data: Vec::new(),
+            needs_input: true,
+        }
+    }
+}
+
+#[pymethods]
+impl Decompressor {
+    #[pyo3(signature = (data, max_length=-1))]
+    fn decompress<'py>(
+        &mut self,
+        py: Python<'py>,
+        data: &PyAny,
+        max_length: isize,
+    ) -> PyResult<&'py PyBytes> {
         let data = to_bytes(data)?;
-        let output = zstd_compress_impl(data, self.level)?;
+        if self.eof {
+            return Err(PyEOFError::new_err("end of stream already reached"));
+        }
+        let mut limit = usize::try_from(max_length).unwrap_or(usize::MAX);
+        if let Some(max_output_size) = self.max_output_size {
+            limit = limit.min(
+                max_output_size
+                    .saturating_sub(self.total_out)
+                    .saturating_add(1),
+            );
+        }
+        let buffered;
+        let input = if self.pending.is_empty() {
+            &*data
+        } else {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.extend_from_slice(&data);
+            buffered = pending;
+            &buffered
+        };
+        let mut output = Vec::new();
+        let progress = decode::drive(self.decoder.as_mut(), input, &mut output, limit);
+        self.total_in += progress.consumed;
+        if let Some(err) = progress.error {
+            return Err(CodecError::corrupt(Some(self.codec), Some(self.total_in), err).into());
+        }
+        self.total_out += output.len();
+        if let Some(max_output_size) = self.max_output_size {
+            if self.total_out > max_output_size {
+                return Err(limit_exceeded(max_output_size));
+            }
+        }
+        let rest = &input[progress.consumed..];
+        if progress.finished {
+            self.eof = true;
+            self.needs_input = false;
+            self.unused_data = rest.to_vec();
+        } else if rest.is_empty() {
+            self.needs_input = output.len() < limit;
+        } else {
+            self.needs_input = false;
+            self.pending = rest.to_vec();
+        }
         Ok(PyBytes::new(py, &output))
     }
 
-    fn finish<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
-        Ok(PyBytes::new(py, &[]))
+    #[getter]
+    fn eof(&self) -> bool {
+        self.eof
+    }
+
+    #[getter]
+    fn unused_data<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.unused_data)
+    }
+
+    #[getter]
+    fn needs_input(&self) -> bool {
+        self.needs_input
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct GzipDecompressor;
+
+#[pymethods]
+impl GzipDecompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None))]
+    fn new(max_output_size: Option<usize>) -> (Self, Decompressor) {
+        let decoder = Box::new(GzipStreamDecoder::new());
+        (Self, Decompressor::new("gzip", decoder, max_output_size))
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct DeflateDecompressor;
+
+#[pymethods]
+impl DeflateDecompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None))]
+    fn new(max_output_size: Option<usize>) -> (Self, Decompressor) {
+        let decoder = Box::new(DeflateStreamDecoder::new());
+        (Self, Decompressor::new("deflate", decoder, max_output_size))
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct BrotliDecompressor;
+
+#[pymethods]
+impl BrotliDecompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None))]
+    fn new(max_output_size: Option<usize>) -> (Self, Decompressor) {
+        let decoder = Box::new(BrotliStreamDecoder::new());
+        (Self, Decompressor::new("br", decoder, max_output_size))
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct ZstdDecompressor;
+
+#[pymethods]
+impl ZstdDecompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None, dictionary=None))]
+    fn new(
+        max_output_size: Option<usize>,
+        dictionary: Option<PyRef<'_, ZstdDictionary>>,
+    ) -> PyResult<(Self, Decompressor)> {
+        let dictionary = dictionary.map(|dictionary| dictionary.decoder());
+        let decoder = ZstdStreamDecoder::new(dictionary)
+            .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+        Ok((
+            Self,
+            Decompressor::new("zstd", Box::new(decoder), max_output_size),
+        ))
+    }
+}
+
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct Lz4Decompressor;
+
+#[pymethods]
+impl Lz4Decompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None))]
+    fn new(max_output_size: Option<usize>) -> (Self, Decompressor) {
+        let decoder = Box::new(BlockStreamDecoder::new(Lz4Format::new()));
+        (Self, Decompressor::new("lz4", decoder, max_output_size))
+    }
+}
+
+/// Decodes the Snappy framing format. The format has no end marker, so `eof` stays false.
+#[pyclass(module = "fastcomp", extends = Decompressor)]
+struct SnappyDecompressor;
+
+#[pymethods]
+impl SnappyDecompressor {
+    #[new]
+    #[pyo3(signature = (*, max_output_size=None))]
+    fn new(max_output_size: Option<usize>) -> (Self, Decompressor) {
+        let decoder = Box::new(BlockStreamDecoder::new(SnappyFormat::new()));
+        (Self, Decompressor::new("snappy", decoder, max_output_size))
     }
 }
 
 #[pymodule]
-fn _fastcomp(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+fn _fastcomp(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    errors::register(py, m)?;
+    pool::register_shutdown(py)?;
     m.add_function(wrap_pyfunction!(gzip_compress, m)?)?;
     m.add_function(wrap_pyfunction!(gzip_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(gzip_info, m)?)?;
+    m.add_function(wrap_pyfunction!(deflate_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(deflate_decompress, m)?)?;
     m.add_function(wrap_pyfunction!(brotli_compress, m)?)?;
     m.add_function(wrap_pyfunction!(brotli_decompress, m)?)?;
     m.add_function(wrap_pyfunction!(zstd_compress, m)?)?;
     m.add_function(wrap_pyfunction!(zstd_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(zstd_dict::zstd_train_dictionary, m)?)?;
+    m.add_function(wrap_pyfunction!(seekable::zstd_seekable_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(zstd_frame::zstd_frame_info, m)?)?;
+    m.add_function(wrap_pyfunction!(zstd_frame::iter_zstd_frames, m)?)?;
+    m.add_function(wrap_pyfunction!(lz4_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(lz4_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(snappy_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(snappy_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(adaptive::compress_adaptive, m)?)?;
+    m.add_function(wrap_pyfunction!(compress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(acompress, m)?)?;
+    m.add_function(wrap_pyfunction!(adecompress, m)?)?;
+    m.add_function(wrap_pyfunction!(decompress_into, m)?)?;
+    m.add_function(wrap_pyfunction!(shared_dict::dcb_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(shared_dict::dcb_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(shared_dict::dcz_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(shared_dict::dcz_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        shared_dict::match_available_dictionary,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(negotiate, m)?)?;
+    m.add_function(wrap_pyfunction!(negotiate_compressor, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default_max_output_size, m)?)?;
+    m.add_function(wrap_pyfunction!(get_default_max_output_size, m)?)?;
     m.add_class::<GzipCompressor>()?;
+    m.add_class::<GzipInfo>()?;
+    m.add_class::<zstd_frame::ZstdFrameInfo>()?;
+    m.add_class::<zstd_frame::ZstdFrameIterator>()?;
+    m.add_class::<DeflateCompressor>()?;
     m.add_class::<BrotliCompressor>()?;
+    m.add_class::<BrotliParams>()?;
     m.add_class::<ZstdCompressor>()?;
+    m.add_class::<ZstdSeekableCompressor>()?;
+    m.add_class::<Lz4Compressor>()?;
+    m.add_class::<SnappyCompressor>()?;
+    m.add_class::<GzipDecompressor>()?;
+    m.add_class::<DeflateDecompressor>()?;
+    m.add_class::<BrotliDecompressor>()?;
+    m.add_class::<ZstdDecompressor>()?;
+    m.add_class::<Lz4Decompressor>()?;
+    m.add_class::<SnappyDecompressor>()?;
+    m.add_class::<responder::CompressionResponder>()?;
+    m.add_class::<adaptive::CompressionPolicy>()?;
+    m.add_class::<permessage_deflate::PerMessageDeflate>()?;
+    m.add_class::<precompressed::PrecompressedStore>()?;
+    m.add_class::<zip_stream::ZipStreamWriter>()?;
+    m.add_class::<seekable::SeekableZstdReader>()?;
+    m.add_class::<ZstdDictionary>()?;
+    m.add_class::<ZstdParams>()?;
+    m.add_class::<shared_dict::SharedDictionary>()?;
     Ok(())
 }
@@ -0,0 +1,135 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyMemoryView;
+use std::io::{self, Write};
+
+/// Bytes kept in the scratch chunk once a caller's buffer is full.
+const SCRATCH_SIZE: usize = 32 * 1024;
+
+/// Where decoded bytes go. Decoders write straight into the space `grow` hands out, so a
+/// caller's buffer is filled in place rather than through an intermediate `Vec`.
+pub(crate) trait Output: Write {
+    /// Bytes produced so far.
+    fn len(&self) -> usize;
+    /// Returns space for up to `room` more bytes after the first `len()`.
+    fn grow(&mut self, room: usize) -> &mut [u8];
+    /// Keeps the first `len` bytes, dropping the unused part of the last `grow`.
+    fn truncate(&mut self, len: usize);
+    /// The produced bytes that were kept, for error reports.
+    fn kept(&self) -> &[u8];
+}
+
+impl Output for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn grow(&mut self, room: usize) -> &mut [u8] {
+        let start = Vec::len(self);
+        self.resize(start + room, 0);
+        &mut self[start..]
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn kept(&self) -> &[u8] {
+        self
+    }
+}
+
+/// A caller's buffer as output. Bytes past its end only go through a scratch chunk and
+/// are counted, so an undersized buffer still learns the size it needs.
+pub(crate) struct SliceOutput<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    scratch: Vec<u8>,
+}
+
+impl<'a> SliceOutput<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            scratch: Vec::new(),
+        }
+    }
+}
+
+impl Output for SliceOutput<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn grow(&mut self, room: usize) -> &mut [u8] {
+        if self.len < self.buf.len() {
+            let end = self.buf.len().min(self.len.saturating_add(room));
+            return &mut self.buf[self.len..end];
+        }
+        self.scratch.resize(room.min(SCRATCH_SIZE), 0);
+        &mut self.scratch
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    fn kept(&self) -> &[u8] {
+        &self.buf[..self.len.min(self.buf.len())]
+    }
+}
+
+impl Write for SliceOutput<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(room) = self.buf.get_mut(self.len..) {
+            let fits = room.len().min(data.len());
+            room[..fits].copy_from_slice(&data[..fits]);
+        }
+        self.len += data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A caller's writable buffer, pinned for the length of one call. The buffer protocol is
+/// not in the abi3 API for Python 3.9, so the address comes from a `ctypes` array laid over
+/// the buffer; the array holds a buffer export, which stops the memory from being resized
+/// or released until it is dropped, also while the GIL is released.
+pub(crate) struct OutBuffer {
+    _export: PyObject,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl OutBuffer {
+    /// Raises `TypeError` for read-only or non-contiguous buffers.
+    pub fn new(out: &PyAny) -> PyResult<Self> {
+        let py = out.py();
+        let len: usize = PyMemoryView::from(out)
+            .map_err(|_| PyValueError::new_err("expected a writable bytes-like object"))?
+            .getattr("nbytes")?
+            .extract()?;
+        let ctypes = py.import("ctypes")?;
+        let array = ctypes.getattr("c_char")?.call_method1("__mul__", (len,))?;
+        let export = array.call_method1("from_buffer", (out,))?;
+        let address: usize = ctypes.call_method1("addressof", (export,))?.extract()?;
+        Ok(Self {
+            _export: export.into(),
+            ptr: address as *mut u8,
+            len,
+        })
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        if self.len == 0 {
+            return &mut [];
+        }
+        // SAFETY: the export keeps `len` bytes at `ptr` in place until `self` is dropped, and
+        // the exclusive borrow of `self` rules out a second slice over them.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
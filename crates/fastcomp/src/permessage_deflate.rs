@@ -0,0 +1,421 @@
+use crate::encode::{FlushMode, StreamEncoder};
+use crate::errors::{CodecError, InvalidParameterError};
+use crate::{check_level, limit_exceeded, resolve_max_output_size, text_or_bytes, to_bytes};
+use flate2::{Decompress, FlushDecompress, Status};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+const EXTENSION: &str = "permessage-deflate";
+/// Empty stored block that ends every sync flush. Senders strip it and receivers put it
+/// back (RFC 7692 section 7.2).
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Parameters of one `permessage-deflate` element of `Sec-WebSocket-Extensions`.
+#[derive(Default)]
+struct Params {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+    /// `Some(None)` is the value-less form, which only a client offer may use.
+    server_max_window_bits: Option<Option<u8>>,
+    client_max_window_bits: Option<Option<u8>>,
+}
+
+fn window_bits(value: Option<&str>) -> Result<Option<u8>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let digits = value.trim_matches('"');
+    match digits.parse::<u8>() {
+        Ok(bits) if (8..=15).contains(&bits) && !digits.starts_with('0') => Ok(Some(bits)),
+        _ => Err(format!("invalid window bits: {value}")),
+    }
+}
+
+impl Params {
+    /// Parses the parameters after the extension name, rejecting unknown or repeated ones.
+    fn parse<'a>(params: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (param.trim(), None),
+            };
+            let duplicate = match name.to_ascii_lowercase().as_str() {
+                "server_no_context_takeover" if value.is_none() => {
+                    std::mem::replace(&mut parsed.server_no_context_takeover, true)
+                }
+                "client_no_context_takeover" if value.is_none() => {
+                    std::mem::replace(&mut parsed.client_no_context_takeover, true)
+                }
+                "server_max_window_bits" => parsed
+                    .server_max_window_bits
+                    .replace(window_bits(value)?)
+                    .is_some(),
+                "client_max_window_bits" => parsed
+                    .client_max_window_bits
+                    .replace(window_bits(value)?)
+                    .is_some(),
+                _ => return Err(format!("unsupported {EXTENSION} parameter: {param}")),
+            };
+            if duplicate {
+                return Err(format!("repeated {EXTENSION} parameter: {name}"));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn to_header(&self) -> String {
+        let mut header = EXTENSION.to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        for (name, bits) in [
+            ("server_max_window_bits", self.server_max_window_bits),
+            ("client_max_window_bits", self.client_max_window_bits),
+        ] {
+            match bits {
+                Some(Some(bits)) => header.push_str(&format!("; {name}={bits}")),
+                Some(None) => header.push_str(&format!("; {name}")),
+                None => {}
+            }
+        }
+        header
+    }
+}
+
+/// Splits a `Sec-WebSocket-Extensions` value into `permessage-deflate` elements, each given
+/// as its parameter strings. Other extensions are skipped.
+fn extension_offers(header: &str) -> Vec<Vec<&str>> {
+    header
+        .split(',')
+        .filter_map(|element| {
+            let mut parts = element.split(';');
+            let name = parts.next()?.trim();
+            name.eq_ignore_ascii_case(EXTENSION).then(|| {
+                parts
+                    .map(str::trim)
+                    .filter(|part| !part.is_empty())
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+/// The `permessage-deflate` WebSocket extension (RFC 7692) for one connection. A server
+/// calls `negotiate` with the client's offer; a client sends `offer()` and passes the
+/// server's answer to `accept`. The local compressor always uses a 32 KiB window, so
+/// offers that limit it further are declined.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct PerMessageDeflate {
+    is_server: bool,
+    level: u32,
+    /// Settings requested at construction; the agreed ones below replace them.
+    requested: Params,
+    #[pyo3(get)]
+    enabled: bool,
+    #[pyo3(get)]
+    server_no_context_takeover: bool,
+    #[pyo3(get)]
+    client_no_context_takeover: bool,
+    #[pyo3(get)]
+    server_max_window_bits: u8,
+    #[pyo3(get)]
+    client_max_window_bits: u8,
+    max_output_size: Option<usize>,
+    encoder: Option<StreamEncoder>,
+    decoder: Decompress,
+}
+
+impl PerMessageDeflate {
+    fn local_no_context_takeover(&self) -> bool {
+        if self.is_server {
+            self.server_no_context_takeover
+        } else {
+            self.client_no_context_takeover
+        }
+    }
+
+    fn remote_no_context_takeover(&self) -> bool {
+        if self.is_server {
+            self.client_no_context_takeover
+        } else {
+            self.server_no_context_takeover
+        }
+    }
+
+    /// Builds the server's answer to one client offer, or `None` to decline it.
+    fn answer(&self, offer: &Params) -> Option<Params> {
+        if offer
+            .server_max_window_bits
+            .is_some_and(|bits| bits.is_some_and(|bits| bits < 15))
+        {
+            return None;
+        }
+        let client_max_window_bits = match (
+            offer.client_max_window_bits,
+            self.requested.client_max_window_bits,
+        ) {
+            (Some(offered), Some(requested)) => {
+                Some(Some(offered.unwrap_or(15).min(requested.unwrap_or(15))))
+            }
+            _ => None,
+        };
+        Some(Params {
+            server_no_context_takeover: offer.server_no_context_takeover
+                || self.requested.server_no_context_takeover,
+            client_no_context_takeover: self.requested.client_no_context_takeover,
+            server_max_window_bits: offer.server_max_window_bits.map(|_| Some(15)),
+            client_max_window_bits,
+        })
+    }
+
+    fn apply(&mut self, agreed: &Params) {
+        self.enabled = true;
+        self.server_no_context_takeover = agreed.server_no_context_takeover;
+        self.client_no_context_takeover = agreed.client_no_context_takeover;
+        self.server_max_window_bits = agreed.server_max_window_bits.flatten().unwrap_or(15);
+        self.client_max_window_bits = agreed.client_max_window_bits.flatten().unwrap_or(15);
+    }
+
+    fn ensure_enabled(&self) -> PyResult<()> {
+        if self.enabled {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(format!(
+                "{EXTENSION} has not been negotiated"
+            )))
+        }
+    }
+
+    /// Inflates one message. Output is capped at `limit + 1` bytes, so an oversized message is
+    /// rejected without inflating the rest of it.
+    fn inflate(&mut self, payload: &[u8], limit: Option<usize>) -> PyResult<Vec<u8>> {
+        let cap = limit.map_or(usize::MAX, |limit| limit.saturating_add(1));
+        let mut output = Vec::new();
+        for mut input in [payload, &TAIL[..]] {
+            loop {
+                let start = output.len();
+                let room = CHUNK_SIZE.min(cap - start);
+                output.resize(start + room, 0);
+                let (total_in, total_out) = (self.decoder.total_in(), self.decoder.total_out());
+                let status = self
+                    .decoder
+                    .decompress(input, &mut output[start..], FlushDecompress::Sync)
+                    .map_err(|err| CodecError::corrupt(Some("deflate"), None, err))?;
+                let consumed = (self.decoder.total_in() - total_in) as usize;
+                let produced = (self.decoder.total_out() - total_out) as usize;
+                output.truncate(start + produced);
+                input = &input[consumed..];
+                // A block marked final ends the deflate stream; the next message starts anew.
+                if status == Status::StreamEnd {
+                    self.decoder.reset(false);
+                    return Ok(output);
+                }
+                if let Some(limit) = limit.filter(|limit| output.len() > *limit) {
+                    self.decoder.reset(false);
+                    return Err(limit_exceeded(limit));
+                }
+                // A full chunk may leave more output pending even once the input is used up.
+                if input.is_empty() && produced < room {
+                    break;
+                }
+                if consumed == 0 && produced < room {
+                    let message = "corrupt permessage-deflate payload";
+                    return Err(CodecError::corrupt(Some("deflate"), None, message).into());
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[pymethods]
+impl PerMessageDeflate {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        is_server=true,
+        level=5,
+        server_no_context_takeover=false,
+        client_no_context_takeover=false,
+        server_max_window_bits=None,
+        client_max_window_bits=None,
+        max_output_size=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        is_server: bool,
+        level: u32,
+        server_no_context_takeover: bool,
+        client_no_context_takeover: bool,
+        server_max_window_bits: Option<u8>,
+        client_max_window_bits: Option<u8>,
+        max_output_size: Option<usize>,
+    ) -> PyResult<Self> {
+        check_level("deflate", level)?;
+        for bits in [server_max_window_bits, client_max_window_bits]
+            .into_iter()
+            .flatten()
+        {
+            if !(8..=15).contains(&bits) {
+                return Err(InvalidParameterError::new_err(
+                    "max_window_bits must be between 8 and 15",
+                ));
+            }
+        }
+        let local_bits = if is_server {
+            server_max_window_bits
+        } else {
+            client_max_window_bits
+        };
+        if local_bits.is_some_and(|bits| bits < 15) {
+            return Err(InvalidParameterError::new_err(
+                "the local compressor always uses 15 window bits",
+            ));
+        }
+        Ok(Self {
+            is_server,
+            level,
+            requested: Params {
+                server_no_context_takeover,
+                client_no_context_takeover,
+                server_max_window_bits: server_max_window_bits.map(Some),
+                client_max_window_bits: client_max_window_bits.map(Some),
+            },
+            enabled: false,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            server_max_window_bits: 15,
+            client_max_window_bits: 15,
+            max_output_size: resolve_max_output_size(max_output_size),
+            encoder: None,
+            decoder: Decompress::new(false),
+        })
+    }
+
+    /// Server side: picks the first acceptable `permessage-deflate` offer from a client's
+    /// `Sec-WebSocket-Extensions` header and returns the value to answer with, or `None`
+    /// when every offer is declined.
+    fn negotiate(&mut self, header: &PyAny) -> PyResult<Option<String>> {
+        if !self.is_server {
+            return Err(PyValueError::new_err(
+                "negotiate is only available to servers",
+            ));
+        }
+        let header = String::from_utf8_lossy(&text_or_bytes(header)?).into_owned();
+        for offer in extension_offers(&header) {
+            let Ok(offer) = Params::parse(offer.into_iter()) else {
+                continue;
+            };
+            if let Some(agreed) = self.answer(&offer) {
+                self.apply(&agreed);
+                return Ok(Some(agreed.to_header()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Client side: the `Sec-WebSocket-Extensions` value to send in the handshake.
+    fn offer(&self) -> PyResult<String> {
+        if self.is_server {
+            return Err(PyValueError::new_err("offer is only available to clients"));
+        }
+        Ok(self.requested.to_header())
+    }
+
+    /// Client side: applies the server's `Sec-WebSocket-Extensions` answer, raising
+    /// `ValueError` when it is not a valid response to `offer()`.
+    fn accept(&mut self, header: &PyAny) -> PyResult<()> {
+        if self.is_server {
+            return Err(PyValueError::new_err("accept is only available to clients"));
+        }
+        let header = String::from_utf8_lossy(&text_or_bytes(header)?).into_owned();
+        let offers = extension_offers(&header);
+        let [response] = offers.as_slice() else {
+            return Err(PyValueError::new_err(format!(
+                "expected a single {EXTENSION} response"
+            )));
+        };
+        let response = Params::parse(response.iter().copied()).map_err(PyValueError::new_err)?;
+        // Only a 15-bit client window can be honoured, and only if it was offered.
+        match response.client_max_window_bits {
+            None => {}
+            Some(Some(15)) if self.requested.client_max_window_bits.is_some() => {}
+            Some(_) => {
+                return Err(PyValueError::new_err(
+                    "server sent an unacceptable client_max_window_bits",
+                ))
+            }
+        }
+        if response.server_max_window_bits == Some(None) {
+            return Err(PyValueError::new_err(
+                "server_max_window_bits needs a value",
+            ));
+        }
+        let requested = &self.requested;
+        if requested.server_no_context_takeover && !response.server_no_context_takeover {
+            return Err(PyValueError::new_err(
+                "server ignored server_no_context_takeover",
+            ));
+        }
+        if let Some(Some(limit)) = requested.server_max_window_bits {
+            if response
+                .server_max_window_bits
+                .flatten()
+                .map_or(true, |bits| bits > limit)
+            {
+                return Err(PyValueError::new_err(
+                    "server ignored server_max_window_bits",
+                ));
+            }
+        }
+        let client_no_context_takeover =
+            response.client_no_context_takeover || requested.client_no_context_takeover;
+        self.apply(&Params {
+            client_no_context_takeover,
+            ..response
+        });
+        Ok(())
+    }
+
+    /// Compresses one message payload, without the trailing `00 00 ff ff`.
+    fn compress<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
+        self.ensure_enabled()?;
+        let data = to_bytes(data)?;
+        let level = self.level;
+        let encoder = self
+            .encoder
+            .get_or_insert_with(|| StreamEncoder::deflate(level, true));
+        let mut output = encoder
+            .update(&data)
+            .map_err(|err| CodecError::encode(Some("deflate"), err))?;
+        output.extend(
+            encoder
+                .flush(FlushMode::Sync)
+                .map_err(|err| CodecError::encode(Some("deflate"), err))?,
+        );
+        if output.ends_with(&TAIL) {
+            output.truncate(output.len() - TAIL.len());
+        }
+        if self.local_no_context_takeover() {
+            self.encoder = None;
+        }
+        Ok(PyBytes::new(py, &output))
+    }
+
+    /// Decompresses one message payload after restoring the trailing `00 00 ff ff`.
+    fn decompress<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
+        self.ensure_enabled()?;
+        let data = to_bytes(data)?;
+        let output = self.inflate(&data, self.max_output_size)?;
+        if self.remote_no_context_takeover() {
+            self.decoder.reset(false);
+        }
+        Ok(PyBytes::new(py, &output))
+    }
+}
@@ -0,0 +1,190 @@
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+/// Jobs waiting for a worker beyond this many per thread make the caller wait for a slot.
+const QUEUE_PER_WORKER: usize = 64;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed set of worker threads fed through a bounded queue.
+struct Pool {
+    sender: SyncSender<Job>,
+}
+
+impl Pool {
+    fn new() -> Self {
+        let workers = thread::available_parallelism().map_or(4, |n| n.get());
+        let (sender, receiver) = mpsc::sync_channel::<Job>(workers * QUEUE_PER_WORKER);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for index in 0..workers {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("fastcomp-worker-{index}"))
+                .spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+                .expect("failed to spawn fastcomp worker thread");
+        }
+        Self { sender }
+    }
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(Pool::new)
+}
+
+/// Whether the interpreter is shutting down, and how many workers currently hold or wait
+/// for the GIL. Once `closing` is set no worker takes the GIL again.
+#[derive(Default)]
+struct Gate {
+    closing: bool,
+    active: usize,
+}
+
+fn gate() -> &'static (Mutex<Gate>, Condvar) {
+    static GATE: OnceLock<(Mutex<Gate>, Condvar)> = OnceLock::new();
+    GATE.get_or_init(Default::default)
+}
+
+/// Runs `f` with the GIL unless the interpreter has started shutting down, in which case
+/// the result is dropped; taking the GIL during finalization aborts the process.
+fn with_gil_unless_closing(f: impl FnOnce(Python<'_>)) {
+    let (lock, done) = gate();
+    {
+        let mut gate = lock.lock().unwrap_or_else(|err| err.into_inner());
+        if gate.closing {
+            return;
+        }
+        gate.active += 1;
+    }
+    Python::with_gil(f);
+    let mut gate = lock.lock().unwrap_or_else(|err| err.into_inner());
+    gate.active -= 1;
+    done.notify_all();
+}
+
+/// Registered with `atexit`: stops workers from taking the GIL and waits for those that
+/// already are, so that none of them runs into interpreter finalization.
+#[pyfunction]
+fn shutdown(py: Python<'_>) {
+    py.allow_threads(|| {
+        let (lock, done) = gate();
+        let mut gate = lock.lock().unwrap_or_else(|err| err.into_inner());
+        gate.closing = true;
+        while gate.active > 0 {
+            gate = done.wait(gate).unwrap_or_else(|err| err.into_inner());
+        }
+    });
+}
+
+pub(crate) fn register_shutdown(py: Python<'_>) -> PyResult<()> {
+    let shutdown = wrap_pyfunction!(shutdown, py)?;
+    py.import("atexit")?.call_method1("register", (shutdown,))?;
+    Ok(())
+}
+
+/// Done callback that records a cancelled future, so its job is skipped if still queued.
+#[pyclass]
+struct CancelFlag(Arc<AtomicBool>);
+
+#[pymethods]
+impl CancelFlag {
+    fn __call__(&self, future: &PyAny) -> PyResult<()> {
+        if future.call_method0("cancelled")?.is_truthy()? {
+            self.0.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Completes `future` on its event loop unless it was cancelled in the meantime.
+#[pyfunction]
+fn resolve_future(future: &PyAny, result: &PyAny, failed: bool) -> PyResult<()> {
+    if future.call_method0("done")?.is_truthy()? {
+        return Ok(());
+    }
+    let method = if failed {
+        "set_exception"
+    } else {
+        "set_result"
+    };
+    future.call_method1(method, (result,))?;
+    Ok(())
+}
+
+/// Runs `work` on the worker pool and returns an asyncio future, bound to the running
+/// event loop, that resolves to its output. When the queue is full the caller waits for a
+/// free slot.
+pub(crate) fn spawn<F>(py: Python<'_>, work: F) -> PyResult<PyObject>
+where
+    F: FnOnce() -> PyResult<Vec<u8>> + Send + 'static,
+{
+    let event_loop: PyObject = py
+        .import("asyncio")?
+        .call_method0("get_running_loop")?
+        .into();
+    let future: PyObject = event_loop.call_method0(py, "create_future")?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    future.call_method1(
+        py,
+        "add_done_callback",
+        (CancelFlag(Arc::clone(&cancelled)),),
+    )?;
+    let job_future = future.clone_ref(py);
+    let job: Job = Box::new(move || {
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        let output = work();
+        with_gil_unless_closing(|py| {
+            // Nothing awaits the future once its loop is closed, so the result is dropped.
+            if event_loop
+                .call_method0(py, "is_closed")
+                .and_then(|closed| closed.is_truthy(py))
+                .unwrap_or(true)
+            {
+                return;
+            }
+            let (result, failed) = match output {
+                Ok(output) => (PyBytes::new(py, &output).into_py(py), false),
+                Err(err) => (err.into_value(py).into_py(py), true),
+            };
+            let resolve = match wrap_pyfunction!(resolve_future, py) {
+                Ok(resolve) => resolve,
+                Err(err) => return err.write_unraisable(py, None),
+            };
+            // The loop can still close between the check above and this call.
+            let _ = event_loop.call_method1(
+                py,
+                "call_soon_threadsafe",
+                (resolve, job_future, result, failed),
+            );
+        });
+    });
+    let sender = &pool().sender;
+    let job = match sender.try_send(job) {
+        Ok(()) => return Ok(future),
+        Err(TrySendError::Full(job)) => job,
+        Err(TrySendError::Disconnected(_)) => {
+            return Err(PyRuntimeError::new_err(
+                "fastcomp worker pool has shut down",
+            ))
+        }
+    };
+    py.allow_threads(|| sender.send(job))
+        .map_err(|_| PyRuntimeError::new_err("fastcomp worker pool has shut down"))?;
+    Ok(future)
+}
@@ -0,0 +1,254 @@
+use crate::{accept, compress_coding_impl, text_or_bytes, to_bytes, unsupported_coding};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+
+/// Levels used when the caller does not pass `levels`; assets are compressed once, so the
+/// slowest settings pay off.
+fn default_level(coding: &str) -> i32 {
+    match coding {
+        "br" => 11,
+        "zstd" => 19,
+        _ => 9,
+    }
+}
+
+struct Entry {
+    identity: Py<PyBytes>,
+    /// Compressed variants in server preference order, omitting any that did not shrink.
+    variants: Vec<(String, Py<PyBytes>)>,
+    size: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, Entry>,
+    size: usize,
+    clock: u64,
+}
+
+impl Cache {
+    fn touch(&mut self, key: &str) -> Option<&Entry> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some(entry)
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.size -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn evict_until(&mut self, budget: usize) {
+        while self.size > budget {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+/// Static assets compressed once per coding and kept, with their original bytes, under a
+/// memory budget with least-recently-used eviction. Entries are keyed by ETag or, by
+/// default, the SHA-256 of the content, so a key is assumed to always name the same bytes.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct PrecompressedStore {
+    #[pyo3(get)]
+    max_bytes: usize,
+    codings: Vec<(String, i32)>,
+    cache: Mutex<Cache>,
+}
+
+impl PrecompressedStore {
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[pymethods]
+impl PrecompressedStore {
+    #[new]
+    #[pyo3(signature = (max_bytes, *, codings=None, levels=None))]
+    fn new(
+        max_bytes: usize,
+        codings: Option<Vec<String>>,
+        levels: Option<HashMap<String, i32>>,
+    ) -> PyResult<Self> {
+        let codings = match codings {
+            Some(codings) => codings
+                .into_iter()
+                .map(|coding| coding.to_ascii_lowercase())
+                .collect(),
+            None => accept::DEFAULT_PREFERENCE
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>(),
+        };
+        if let Some(coding) = codings
+            .iter()
+            .find(|coding| !accept::is_compressor_coding(coding))
+        {
+            return Err(unsupported_coding(coding));
+        }
+        let levels: HashMap<String, i32> = levels
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(coding, level)| (coding.to_ascii_lowercase(), level))
+            .collect();
+        if let Some(coding) = levels.keys().find(|coding| !codings.contains(coding)) {
+            return Err(unsupported_coding(coding));
+        }
+        let codings = codings
+            .into_iter()
+            .map(|coding| {
+                let level = levels
+                    .get(&coding)
+                    .copied()
+                    .unwrap_or_else(|| default_level(&coding));
+                (coding, level)
+            })
+            .collect();
+        Ok(Self {
+            max_bytes,
+            codings,
+            cache: Mutex::new(Cache::default()),
+        })
+    }
+
+    /// Compresses `data` with every configured coding and caches the results, returning the
+    /// key. Nothing is recompressed when the key is already cached, and an asset larger than
+    /// the whole budget is not cached at all.
+    #[pyo3(signature = (data, *, key=None))]
+    fn add(&self, py: Python<'_>, data: &PyAny, key: Option<String>) -> PyResult<String> {
+        let bytes = to_bytes(data)?;
+        let key = key.unwrap_or_else(|| {
+            Sha256::digest(&bytes)
+                .iter()
+                .fold(String::with_capacity(64), |mut key, byte| {
+                    let _ = write!(key, "{byte:02x}");
+                    key
+                })
+        });
+        if self.cache().touch(&key).is_some() {
+            return Ok(key);
+        }
+        let compressed = py.allow_threads(|| {
+            self.codings
+                .iter()
+                .map(|(coding, level)| {
+                    let mut output = Vec::new();
+                    compress_coding_impl(coding, &bytes, &mut output, Some(*level))
+                        .map(|()| (coding.clone(), output))
+                })
+                .collect::<PyResult<Vec<_>>>()
+        })?;
+        let variants: Vec<(String, Py<PyBytes>)> = compressed
+            .into_iter()
+            .filter(|(_, output)| output.len() < bytes.len())
+            .map(|(coding, output)| (coding, PyBytes::new(py, &output).into()))
+            .collect();
+        let size = bytes.len()
+            + variants
+                .iter()
+                .map(|(_, output)| output.as_ref(py).as_bytes().len())
+                .sum::<usize>();
+        if size > self.max_bytes {
+            return Ok(key);
+        }
+        let identity = match data.downcast::<PyBytes>() {
+            Ok(data) => data.into(),
+            Err(_) => PyBytes::new(py, &bytes).into(),
+        };
+        let mut cache = self.cache();
+        cache.remove(&key);
+        cache.evict_until(self.max_bytes - size);
+        cache.clock += 1;
+        let entry = Entry {
+            identity,
+            variants,
+            size,
+            last_used: cache.clock,
+        };
+        cache.entries.insert(key.clone(), entry);
+        cache.size += size;
+        Ok(key)
+    }
+
+    /// Returns the best cached variant of `key` for an `Accept-Encoding` value as
+    /// `(coding, body)`, with `coding` set to `None` for the original bytes, or `None` when
+    /// the key is not cached.
+    fn get(
+        &self,
+        py: Python<'_>,
+        key: &str,
+        accept_encoding: &PyAny,
+    ) -> PyResult<Option<(Option<String>, Py<PyBytes>)>> {
+        let header = text_or_bytes(accept_encoding)?;
+        let header = String::from_utf8_lossy(&header);
+        let mut cache = self.cache();
+        let Some(entry) = cache.touch(key) else {
+            return Ok(None);
+        };
+        let available: Vec<&str> = entry
+            .variants
+            .iter()
+            .map(|(coding, _)| coding.as_str())
+            .collect();
+        let chosen = accept::negotiate(&header, &available);
+        let body = chosen
+            .and_then(|chosen| entry.variants.iter().find(|(coding, _)| coding == chosen))
+            .map_or(&entry.identity, |(_, output)| output);
+        Ok(Some((chosen.map(str::to_string), body.clone_ref(py))))
+    }
+
+    /// Codings cached for `key`, in preference order, or `None` when it is not cached.
+    fn codings(&self, key: &str) -> Option<Vec<String>> {
+        let cache = self.cache();
+        let entry = cache.entries.get(key)?;
+        Some(
+            entry
+                .variants
+                .iter()
+                .map(|(coding, _)| coding.clone())
+                .collect(),
+        )
+    }
+
+    fn remove(&self, key: &str) -> bool {
+        self.cache().remove(key)
+    }
+
+    fn clear(&self) {
+        *self.cache() = Cache::default();
+    }
+
+    /// Bytes held by cached originals and variants.
+    #[getter]
+    fn size(&self) -> usize {
+        self.cache().size
+    }
+
+    fn __len__(&self) -> usize {
+        self.cache().entries.len()
+    }
+
+    fn __contains__(&self, key: &str) -> bool {
+        self.cache().entries.contains_key(key)
+    }
+}
@@ -0,0 +1,357 @@
+use crate::accept;
+use crate::adaptive::{CompressionPolicy, Decision};
+use crate::encode::StreamEncoder;
+use crate::errors::CodecError;
+use crate::{to_bytes, unsupported_coding};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple};
+use std::borrow::Cow;
+
+/// Media types compressed when the caller does not pass `content_types`. Entries ending
+/// in `/` match a whole top-level type.
+const DEFAULT_CONTENT_TYPES: [&str; 8] = [
+    "text/",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "application/xhtml+xml",
+    "application/ld+json",
+    "application/manifest+json",
+    "image/svg+xml",
+];
+
+type Headers = Vec<(Vec<u8>, Vec<u8>)>;
+
+enum State {
+    Start,
+    /// `sample` is set when the policy wants the first body chunk checked for entropy.
+    Pending {
+        start: Py<PyDict>,
+        coding: String,
+        sample: bool,
+    },
+    Compressing(StreamEncoder),
+    Passthrough,
+}
+
+fn header<'a>(headers: &'a Headers, name: &[u8]) -> Option<&'a [u8]> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_slice())
+}
+
+fn has_token(value: &[u8], token: &str) -> bool {
+    String::from_utf8_lossy(value)
+        .split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
+
+fn read_headers(message: &PyDict) -> PyResult<Headers> {
+    let mut headers = Vec::new();
+    let Some(items) = message.get_item("headers")? else {
+        return Ok(headers);
+    };
+    for item in items.iter()? {
+        let item = item?;
+        let name = to_bytes(item.get_item(0)?)?;
+        let value = to_bytes(item.get_item(1)?)?;
+        headers.push((name.to_vec(), value.to_vec()));
+    }
+    Ok(headers)
+}
+
+fn with_headers(py: Python<'_>, message: &PyDict, headers: &Headers) -> PyResult<Py<PyDict>> {
+    let items = headers
+        .iter()
+        .map(|(name, value)| PyTuple::new(py, [PyBytes::new(py, name), PyBytes::new(py, value)]));
+    let message = message.copy()?;
+    message.set_item("headers", PyList::new(py, items))?;
+    Ok(message.into())
+}
+
+fn with_body(py: Python<'_>, message: &PyDict, body: &[u8], more_body: bool) -> PyResult<PyObject> {
+    let message = message.copy()?;
+    message.set_item("body", PyBytes::new(py, body))?;
+    message.set_item("more_body", more_body)?;
+    Ok(message.into())
+}
+
+fn body_of(message: &PyDict) -> PyResult<Cow<'_, [u8]>> {
+    match message.get_item("body")? {
+        Some(body) => to_bytes(body),
+        None => Ok(Cow::Borrowed(&[])),
+    }
+}
+
+fn more_body_of(message: &PyDict) -> PyResult<bool> {
+    match message.get_item("more_body")? {
+        Some(more_body) => more_body.is_truthy(),
+        None => Ok(false),
+    }
+}
+
+/// Lowercased media type of a `Content-Type` value, without parameters.
+pub(crate) fn media_type(content_type: &[u8]) -> String {
+    String::from_utf8_lossy(content_type)
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Matches a media type against a pattern, where a pattern ending in `/` covers a whole
+/// top-level type.
+pub(crate) fn media_type_matches(pattern: &str, media_type: &str) -> bool {
+    if pattern.ends_with('/') {
+        media_type.starts_with(pattern)
+    } else {
+        media_type == pattern
+    }
+}
+
+/// Adds `Accept-Encoding` to the `Vary` header unless it is already covered.
+fn add_vary(headers: &mut Headers) {
+    match headers
+        .iter_mut()
+        .find(|(name, _)| name.eq_ignore_ascii_case(b"vary"))
+    {
+        Some((_, value)) => {
+            if !has_token(value, "accept-encoding") && !has_token(value, "*") {
+                value.extend_from_slice(b", Accept-Encoding");
+            }
+        }
+        None => headers.push((b"vary".to_vec(), b"Accept-Encoding".to_vec())),
+    }
+}
+
+/// Applies response compression to a stream of ASGI `send` messages. An optional
+/// `CompressionPolicy` is consulted for eligible content types: `never` skips compression
+/// and `auto` checks the first body chunk before committing to it.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct CompressionResponder {
+    accept_encoding: String,
+    minimum_size: usize,
+    content_types: Vec<String>,
+    available: Vec<String>,
+    policy: Option<CompressionPolicy>,
+    encoding: Option<String>,
+    state: State,
+}
+
+impl CompressionResponder {
+    fn content_type_allowed(&self, content_type: &[u8]) -> bool {
+        let media_type = media_type(content_type);
+        self.content_types
+            .iter()
+            .any(|allowed| media_type_matches(allowed, &media_type))
+    }
+
+    fn start(&mut self, py: Python<'_>, message: &PyDict) -> PyResult<Vec<PyObject>> {
+        let mut headers = read_headers(message)?;
+        let already_encoded = header(&headers, b"content-encoding")
+            .is_some_and(|value| !value.trim_ascii().eq_ignore_ascii_case(b"identity"));
+        let no_transform = header(&headers, b"cache-control")
+            .is_some_and(|value| has_token(value, "no-transform"));
+        let content_type = header(&headers, b"content-type");
+        let decision = self
+            .policy
+            .as_ref()
+            .map_or(Decision::Always, |policy| policy.decision(content_type));
+        let eligible = !already_encoded
+            && !no_transform
+            && decision != Decision::Never
+            && content_type.is_some_and(|value| self.content_type_allowed(value));
+        if !eligible {
+            self.state = State::Passthrough;
+            return Ok(vec![message.into()]);
+        }
+
+        add_vary(&mut headers);
+        let start = with_headers(py, message, &headers)?;
+        let available: Vec<&str> = self.available.iter().map(String::as_str).collect();
+        let too_small = header(&headers, b"content-length")
+            .and_then(|value| {
+                std::str::from_utf8(value)
+                    .ok()?
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+            })
+            .is_some_and(|length| length < self.minimum_size);
+        match accept::negotiate(&self.accept_encoding, &available) {
+            Some(coding) if !too_small => {
+                self.state = State::Pending {
+                    start,
+                    coding: coding.to_string(),
+                    sample: decision == Decision::Auto,
+                };
+                Ok(Vec::new())
+            }
+            _ => {
+                self.state = State::Passthrough;
+                Ok(vec![start.into_py(py)])
+            }
+        }
+    }
+
+    fn first_body(
+        &mut self,
+        py: Python<'_>,
+        start: Py<PyDict>,
+        coding: String,
+        sample: bool,
+        message: &PyDict,
+    ) -> PyResult<Vec<PyObject>> {
+        let body = body_of(message)?;
+        let more_body = more_body_of(message)?;
+        let incompressible = sample
+            && self
+                .policy
+                .as_ref()
+                .is_some_and(|policy| !policy.compressible(&body));
+        if incompressible || (!more_body && (body.is_empty() || body.len() < self.minimum_size)) {
+            self.state = State::Passthrough;
+            return Ok(vec![start.into_py(py), message.into()]);
+        }
+
+        let mut encoder =
+            StreamEncoder::for_coding(&coding).map_err(|err| CodecError::encode(None, err))?;
+        let mut chunk = encoder
+            .update(&body)
+            .map_err(|err| CodecError::encode(Some(encoder.coding()), err))?;
+        let start = start.as_ref(py);
+        let mut headers = read_headers(start)?;
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case(b"content-length"));
+        headers.push((b"content-encoding".to_vec(), coding.as_bytes().to_vec()));
+        if more_body {
+            self.state = State::Compressing(encoder);
+        } else {
+            let coding = encoder.coding();
+            let tail = encoder
+                .finish()
+                .map_err(|err| CodecError::encode(Some(coding), err))?;
+            chunk.extend_from_slice(&tail);
+            headers.push((
+                b"content-length".to_vec(),
+                chunk.len().to_string().into_bytes(),
+            ));
+            self.state = State::Passthrough;
+        }
+        self.encoding = Some(coding);
+        let start = with_headers(py, start, &headers)?;
+        Ok(vec![
+            start.into_py(py),
+            with_body(py, message, &chunk, more_body)?,
+        ])
+    }
+}
+
+#[pymethods]
+impl CompressionResponder {
+    #[new]
+    #[pyo3(signature = (
+        accept_encoding,
+        *,
+        minimum_size=500,
+        content_types=None,
+        available=None,
+        policy=None,
+    ))]
+    fn new(
+        accept_encoding: &PyAny,
+        minimum_size: usize,
+        content_types: Option<Vec<String>>,
+        available: Option<Vec<String>>,
+        policy: Option<CompressionPolicy>,
+    ) -> PyResult<Self> {
+        let accept_encoding = String::from_utf8_lossy(&to_bytes(accept_encoding)?).into_owned();
+        let content_types = match content_types {
+            Some(content_types) => content_types
+                .into_iter()
+                .map(|content_type| content_type.to_ascii_lowercase())
+                .collect(),
+            None => DEFAULT_CONTENT_TYPES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        };
+        let available = match available {
+            Some(available) => available,
+            None => accept::DEFAULT_PREFERENCE
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        };
+        if let Some(coding) = available
+            .iter()
+            .find(|coding| !accept::is_compressor_coding(coding))
+        {
+            return Err(unsupported_coding(coding));
+        }
+        Ok(Self {
+            accept_encoding,
+            minimum_size,
+            content_types,
+            available,
+            policy,
+            encoding: None,
+            state: State::Start,
+        })
+    }
+
+    /// Content coding applied to the response, once the first body chunk has been seen.
+    #[getter]
+    fn encoding(&self) -> Option<String> {
+        self.encoding.clone()
+    }
+
+    /// Feeds one ASGI send message and returns the messages to forward in its place.
+    fn send(&mut self, py: Python<'_>, message: &PyDict) -> PyResult<Vec<PyObject>> {
+        let kind = match message.get_item("type")? {
+            Some(kind) => kind.extract::<String>()?,
+            None => return Err(PyValueError::new_err("ASGI message has no type")),
+        };
+        match std::mem::replace(&mut self.state, State::Passthrough) {
+            State::Start if kind == "http.response.start" => self.start(py, message),
+            State::Pending {
+                start,
+                coding,
+                sample,
+            } if kind == "http.response.body" => {
+                self.first_body(py, start, coding, sample, message)
+            }
+            // Anything else (a pathsend, say) settles the response uncompressed, behind the
+            // start it must follow.
+            State::Pending { start, .. } => {
+                self.state = State::Passthrough;
+                Ok(vec![start.into_py(py), message.into()])
+            }
+            State::Compressing(mut encoder) if kind == "http.response.body" => {
+                let coding = encoder.coding();
+                let mut chunk = encoder
+                    .update(&body_of(message)?)
+                    .map_err(|err| CodecError::encode(Some(coding), err))?;
+                let more_body = more_body_of(message)?;
+                if more_body {
+                    self.state = State::Compressing(encoder);
+                    if chunk.is_empty() {
+                        return Ok(Vec::new());
+                    }
+                } else {
+                    let tail = encoder
+                        .finish()
+                        .map_err(|err| CodecError::encode(Some(coding), err))?;
+                    chunk.extend_from_slice(&tail);
+                }
+                Ok(vec![with_body(py, message, &chunk, more_body)?])
+            }
+            state => {
+                self.state = state;
+                Ok(vec![message.into()])
+            }
+        }
+    }
+}
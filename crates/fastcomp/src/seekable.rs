@@ -0,0 +1,343 @@
+use crate::errors::CodecError;
+use crate::{check_level, limit_exceeded, resolve_max_output_size, to_bytes};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::hash::Hasher;
+use std::io;
+use twox_hash::XxHash64;
+
+/// Skippable frame magic reserved for the seek table by the zstd seekable format.
+const SEEK_TABLE_MAGIC: u32 = 0x184d_2a5e;
+const SEEKABLE_MAGIC: u32 = 0x8f92_eab1;
+const CHECKSUM_FLAG: u8 = 0x80;
+const FOOTER_LEN: usize = 9;
+const SKIPPABLE_HEADER_LEN: usize = 8;
+/// Frames hold at most this much uncompressed data unless the caller asks otherwise.
+pub(crate) const DEFAULT_FRAME_SIZE: usize = 1 << 20;
+
+/// Lower 32 bits of the XXH64 of a frame's decompressed content, as the format stores it.
+fn frame_checksum(data: &[u8]) -> u32 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(data);
+    hasher.finish() as u32
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(buf: &[u8]) -> u32 {
+    u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]])
+}
+
+/// Writes the zstd seekable format: input is cut into independent frames of at most
+/// `frame_size` bytes, and `finish` appends the seek table as a skippable frame, so plain
+/// zstd decoders still read the whole stream.
+pub(crate) struct SeekableEncoder {
+    level: i32,
+    frame_size: usize,
+    checksum: bool,
+    pending: Vec<u8>,
+    pub output: Vec<u8>,
+    entries: Vec<(u32, u32, u32)>,
+}
+
+impl SeekableEncoder {
+    pub fn new(level: i32, frame_size: usize, checksum: bool) -> io::Result<Self> {
+        if frame_size == 0 || frame_size > u32::MAX as usize / 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame_size must be between 1 byte and 2 GiB",
+            ));
+        }
+        Ok(Self {
+            level,
+            frame_size,
+            checksum,
+            pending: Vec::new(),
+            output: Vec::new(),
+            entries: Vec::new(),
+        })
+    }
+
+    fn end_frame(&mut self, len: usize) -> io::Result<()> {
+        let content = &self.pending[..len];
+        let frame = zstd::bulk::compress(content, self.level)?;
+        let checksum = if self.checksum {
+            frame_checksum(content)
+        } else {
+            0
+        };
+        self.entries
+            .push((frame.len() as u32, content.len() as u32, checksum));
+        self.output.extend_from_slice(&frame);
+        self.pending.drain(..len);
+        Ok(())
+    }
+
+    pub fn write(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let take = (self.frame_size - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() == self.frame_size {
+                self.end_frame(self.frame_size)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the current frame early so that everything written so far can be decoded.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.end_frame(self.pending.len())?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        self.flush()?;
+        let entry_len = if self.checksum { 12 } else { 8 };
+        let table_len = self.entries.len() * entry_len + FOOTER_LEN;
+        let table_len = u32::try_from(table_len)
+            .map_err(|_| invalid_data("too many frames for a seek table"))?;
+        let mut table = Vec::with_capacity(SKIPPABLE_HEADER_LEN + table_len as usize);
+        table.extend_from_slice(&SEEK_TABLE_MAGIC.to_le_bytes());
+        table.extend_from_slice(&table_len.to_le_bytes());
+        for (compressed, decompressed, checksum) in &self.entries {
+            table.extend_from_slice(&compressed.to_le_bytes());
+            table.extend_from_slice(&decompressed.to_le_bytes());
+            if self.checksum {
+                table.extend_from_slice(&checksum.to_le_bytes());
+            }
+        }
+        table.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        table.push(if self.checksum { CHECKSUM_FLAG } else { 0 });
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        self.output.extend_from_slice(&table);
+        Ok(self.output)
+    }
+}
+
+/// Compresses `data` into the zstd seekable format in one call.
+#[pyfunction]
+#[pyo3(signature = (data, level=None, *, frame_size=DEFAULT_FRAME_SIZE, checksum=true))]
+pub(crate) fn zstd_seekable_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    level: Option<i32>,
+    frame_size: usize,
+    checksum: bool,
+) -> PyResult<Py<PyBytes>> {
+    let level = level.unwrap_or(3);
+    check_level("zstd", level)?;
+    let data = to_bytes(data)?;
+    let output = py
+        .allow_threads(|| {
+            let mut encoder = SeekableEncoder::new(level, frame_size, checksum)?;
+            encoder.write(&data)?;
+            encoder.finish()
+        })
+        .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+/// Position of one frame within the compressed and the decompressed stream.
+struct Frame {
+    offset: u64,
+    compressed: usize,
+    start: u64,
+    decompressed: usize,
+    checksum: Option<u32>,
+}
+
+/// Where the compressed stream is read from: an in-memory buffer, or a binary file object
+/// with `seek` and `read`, so large blobs never have to be loaded whole.
+enum Source {
+    Bytes(Py<PyBytes>),
+    File(PyObject),
+}
+
+impl Source {
+    fn len(&self, py: Python<'_>) -> PyResult<u64> {
+        match self {
+            Self::Bytes(bytes) => Ok(bytes.as_ref(py).as_bytes().len() as u64),
+            Self::File(file) => file.call_method1(py, "seek", (0, 2))?.extract(py),
+        }
+    }
+
+    fn read_at(&self, py: Python<'_>, offset: u64, len: usize) -> PyResult<Vec<u8>> {
+        let data = match self {
+            Self::Bytes(bytes) => {
+                let bytes = bytes.as_ref(py).as_bytes();
+                bytes
+                    .get(offset as usize..offset as usize + len)
+                    .map(<[u8]>::to_vec)
+            }
+            Self::File(file) => {
+                file.call_method1(py, "seek", (offset,))?;
+                let chunk = file.call_method1(py, "read", (len,))?;
+                let chunk = to_bytes(chunk.as_ref(py))?;
+                (chunk.len() == len).then(|| chunk.into_owned())
+            }
+        };
+        data.ok_or_else(|| {
+            CodecError::corrupt(Some("zstd"), None, "truncated zstd seekable stream").into()
+        })
+    }
+}
+
+fn parse_seek_table(footer: &[u8], table: &[u8]) -> Vec<Frame> {
+    let frame_count = read_u32(footer) as usize;
+    let checksums = footer[4] & CHECKSUM_FLAG != 0;
+    let entry_len = if checksums { 12 } else { 8 };
+    let mut frames = Vec::with_capacity(frame_count);
+    let (mut offset, mut start) = (0u64, 0u64);
+    for entry in table.chunks_exact(entry_len) {
+        let compressed = read_u32(entry) as usize;
+        let decompressed = read_u32(&entry[4..]) as usize;
+        frames.push(Frame {
+            offset,
+            compressed,
+            start,
+            decompressed,
+            checksum: checksums.then(|| read_u32(&entry[8..])),
+        });
+        offset += compressed as u64;
+        start += decompressed as u64;
+    }
+    frames
+}
+
+/// Random access to the uncompressed content of a zstd seekable stream. Only the frames
+/// overlapping a requested range are read and decompressed.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct SeekableZstdReader {
+    source: Source,
+    frames: Vec<Frame>,
+    max_output_size: Option<usize>,
+}
+
+#[pymethods]
+impl SeekableZstdReader {
+    #[new]
+    #[pyo3(signature = (source, *, max_output_size=None))]
+    fn new(py: Python<'_>, source: &PyAny, max_output_size: Option<usize>) -> PyResult<Self> {
+        let source = if source.hasattr("seek")? && source.hasattr("read")? {
+            Source::File(source.into())
+        } else {
+            match source.downcast::<PyBytes>() {
+                Ok(bytes) => Source::Bytes(bytes.into()),
+                Err(_) => Source::Bytes(PyBytes::new(py, &to_bytes(source)?).into()),
+            }
+        };
+        let len = source.len(py)?;
+        let invalid = || {
+            PyErr::from(CodecError::corrupt(
+                Some("zstd"),
+                None,
+                "missing zstd seek table",
+            ))
+        };
+        let footer_offset = len.checked_sub(FOOTER_LEN as u64).ok_or_else(invalid)?;
+        let footer = source.read_at(py, footer_offset, FOOTER_LEN)?;
+        if read_u32(&footer[5..]) != SEEKABLE_MAGIC || footer[4] & 0x7c != 0 {
+            return Err(invalid());
+        }
+        let entry_len = if footer[4] & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let table_len = (read_u32(&footer) as u64)
+            .checked_mul(entry_len)
+            .ok_or_else(invalid)?;
+        let table_offset = footer_offset.checked_sub(table_len).ok_or_else(invalid)?;
+        let header_offset = table_offset
+            .checked_sub(SKIPPABLE_HEADER_LEN as u64)
+            .ok_or_else(invalid)?;
+        let header = source.read_at(py, header_offset, SKIPPABLE_HEADER_LEN)?;
+        if read_u32(&header) != SEEK_TABLE_MAGIC
+            || u64::from(read_u32(&header[4..])) != table_len + FOOTER_LEN as u64
+        {
+            return Err(invalid());
+        }
+        let table = source.read_at(py, table_offset, table_len as usize)?;
+        let frames = parse_seek_table(&footer, &table);
+        let compressed: u64 = frames.iter().map(|frame| frame.compressed as u64).sum();
+        if compressed != header_offset {
+            return Err(CodecError::corrupt(
+                Some("zstd"),
+                None,
+                "zstd seek table does not match the stream",
+            )
+            .into());
+        }
+        Ok(Self {
+            source,
+            frames,
+            max_output_size: resolve_max_output_size(max_output_size),
+        })
+    }
+
+    /// Total uncompressed size.
+    #[getter]
+    fn size(&self) -> u64 {
+        self.frames
+            .last()
+            .map_or(0, |frame| frame.start + frame.decompressed as u64)
+    }
+
+    #[getter]
+    fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns uncompressed bytes `start` to `end` (exclusive, clamped to `size`).
+    fn read_range<'py>(&self, py: Python<'py>, start: u64, end: u64) -> PyResult<&'py PyBytes> {
+        let end = end.min(self.size());
+        if start >= end {
+            return Ok(PyBytes::new(py, &[]));
+        }
+        if let Some(limit) = self.max_output_size {
+            if end - start > limit as u64 {
+                return Err(limit_exceeded(limit));
+            }
+        }
+        let first = self
+            .frames
+            .partition_point(|frame| frame.start + frame.decompressed as u64 <= start);
+        let last = self.frames.partition_point(|frame| frame.start < end);
+        let frames = &self.frames[first..last];
+        let compressed = frames
+            .iter()
+            .map(|frame| self.source.read_at(py, frame.offset, frame.compressed))
+            .collect::<PyResult<Vec<_>>>()?;
+        let output = py
+            .allow_threads(|| {
+                let mut output = Vec::new();
+                for (frame, data) in frames.iter().zip(&compressed) {
+                    let content = zstd::bulk::decompress(data, frame.decompressed)?;
+                    if content.len() != frame.decompressed {
+                        return Err(invalid_data(
+                            "zstd frame size does not match the seek table",
+                        ));
+                    }
+                    if frame
+                        .checksum
+                        .is_some_and(|checksum| checksum != frame_checksum(&content))
+                    {
+                        return Err(invalid_data("zstd seekable frame checksum mismatch"));
+                    }
+                    output.extend_from_slice(&content);
+                }
+                Ok(output)
+            })
+            .map_err(|err: io::Error| CodecError::corrupt(Some("zstd"), None, err))?;
+        let base = frames[0].start;
+        Ok(PyBytes::new(
+            py,
+            &output[(start - base) as usize..(end - base) as usize],
+        ))
+    }
+}
@@ -0,0 +1,232 @@
+use crate::brotli_params::BrotliParams;
+use crate::decode::ZstdStreamDecoder;
+use crate::errors::{CodecError, InvalidParameterError};
+use crate::zstd_params::ZstdParams;
+use crate::{brotli_decompress_impl, check_level, decode_all, resolve_max_output_size, to_bytes};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use brotli::enc::{BrotliEncoderParams, StandardAlloc};
+use brotli::{interface, InputReferenceMut, IoReaderWrapper, IoWriterWrapper};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+
+/// Header that starts a `dcb` (dictionary-compressed brotli) body.
+const DCB_MAGIC: [u8; 4] = [0xff, 0x44, 0x43, 0x42];
+/// Header that starts a `dcz` body: a zstd skippable frame holding the 32-byte hash.
+const DCZ_MAGIC: [u8; 8] = [0x5e, 0x2a, 0x4d, 0x18, 0x20, 0x00, 0x00, 0x00];
+const HASH_LEN: usize = 32;
+/// Browsers refuse `dcz` bodies whose window exceeds 8 MiB; zstd levels above 19 ask for
+/// more, so theirs is capped.
+const DCZ_MAX_WINDOW_LOG: u32 = 23;
+
+/// A dictionary for Compression Dictionary Transport, identified by its SHA-256 hash.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct SharedDictionary {
+    data: Vec<u8>,
+    hash: [u8; HASH_LEN],
+}
+
+impl SharedDictionary {
+    /// Splits a framed body into its payload after checking the embedded dictionary hash.
+    fn payload<'a>(&self, data: &'a [u8], magic: &[u8]) -> PyResult<&'a [u8]> {
+        let header_len = magic.len() + HASH_LEN;
+        if data.len() < header_len || !data.starts_with(magic) {
+            return Err(
+                CodecError::corrupt(None, None, "missing dictionary-compressed header").into(),
+            );
+        }
+        if data[magic.len()..header_len] != self.hash {
+            return Err(InvalidParameterError::new_err("dictionary hash mismatch"));
+        }
+        Ok(&data[header_len..])
+    }
+
+    fn framed(&self, magic: &[u8], capacity: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(magic.len() + HASH_LEN + capacity);
+        out.extend_from_slice(magic);
+        out.extend_from_slice(&self.hash);
+        out
+    }
+}
+
+#[pymethods]
+impl SharedDictionary {
+    #[new]
+    fn new(data: &PyAny) -> PyResult<Self> {
+        let data = to_bytes(data)?;
+        Ok(Self {
+            hash: Sha256::digest(&data).into(),
+            data: data.into_owned(),
+        })
+    }
+
+    /// SHA-256 digest of the dictionary bytes.
+    #[getter]
+    fn hash<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.hash)
+    }
+
+    /// The hash as a structured-field byte sequence, as sent in `Available-Dictionary`.
+    #[getter]
+    fn available_dictionary<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, format!(":{}:", STANDARD.encode(self.hash)).as_bytes())
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.data)
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.len()
+    }
+}
+
+fn dcb_compress_impl(
+    out: &mut Vec<u8>,
+    data: &[u8],
+    dictionary: &[u8],
+    params: &BrotliEncoderParams,
+) -> io::Result<()> {
+    let mut input_buffer = [0u8; 4096];
+    let mut output_buffer = [0u8; 4096];
+    let mut nop_callback = |_: &mut interface::PredictionModeContextMap<InputReferenceMut>,
+                            _: &mut [interface::StaticCommand],
+                            _: interface::InputPair,
+                            _: &mut StandardAlloc| ();
+    brotli::BrotliCompressCustomIoCustomDict(
+        &mut IoReaderWrapper(&mut { data }),
+        &mut IoWriterWrapper(out),
+        &mut input_buffer,
+        &mut output_buffer,
+        params,
+        StandardAlloc::default(),
+        &mut nop_callback,
+        dictionary,
+        io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of input"),
+    )?;
+    Ok(())
+}
+
+/// `dcz` loads the dictionary as raw content (a zstd prefix), so bytes that happen to
+/// start with the zstd dictionary magic are not parsed as a structured dictionary.
+fn dcz_compress_impl(
+    out: &mut Vec<u8>,
+    data: &[u8],
+    dictionary: &[u8],
+    level: i32,
+) -> io::Result<()> {
+    let mut encoder = zstd::stream::write::Encoder::with_ref_prefix(out, level, dictionary)?;
+    ZstdParams::default().apply(&mut encoder, Some(data.len()))?;
+    if level > 19 {
+        encoder.window_log(DCZ_MAX_WINDOW_LOG)?;
+    }
+    encoder.write_all(data)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, dictionary, level=5))]
+pub(crate) fn dcb_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    dictionary: PyRef<'_, SharedDictionary>,
+    level: u32,
+) -> PyResult<Py<PyBytes>> {
+    check_level("br", level)?;
+    let data = to_bytes(data)?;
+    let mut params = BrotliParams::default().encoder_params(level);
+    params.lgwin = 24;
+    let mut out = dictionary.framed(&DCB_MAGIC, data.len() / 4);
+    let raw = &dictionary.data;
+    py.allow_threads(|| dcb_compress_impl(&mut out, &data, raw, &params))
+        .map_err(|err| CodecError::encode(Some("br"), err))?;
+    Ok(PyBytes::new(py, &out).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, dictionary, *, max_output_size=None))]
+pub(crate) fn dcb_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    dictionary: PyRef<'_, SharedDictionary>,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let payload = dictionary.payload(&data, &DCB_MAGIC)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let raw = &dictionary.data;
+    let mut output = Vec::new();
+    py.allow_threads(|| brotli_decompress_impl(payload, &mut output, max_output_size, Some(raw)))?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, dictionary, level=3))]
+pub(crate) fn dcz_compress(
+    py: Python<'_>,
+    data: &PyAny,
+    dictionary: PyRef<'_, SharedDictionary>,
+    level: i32,
+) -> PyResult<Py<PyBytes>> {
+    check_level("zstd", level)?;
+    let data = to_bytes(data)?;
+    let mut out = dictionary.framed(&DCZ_MAGIC, data.len() / 4);
+    let raw = &dictionary.data;
+    py.allow_threads(|| dcz_compress_impl(&mut out, &data, raw, level))
+        .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+    Ok(PyBytes::new(py, &out).into())
+}
+
+#[pyfunction]
+#[pyo3(signature = (data, dictionary, *, max_output_size=None))]
+pub(crate) fn dcz_decompress(
+    py: Python<'_>,
+    data: &PyAny,
+    dictionary: PyRef<'_, SharedDictionary>,
+    max_output_size: Option<usize>,
+) -> PyResult<Py<PyBytes>> {
+    let data = to_bytes(data)?;
+    let payload = dictionary.payload(&data, &DCZ_MAGIC)?;
+    let max_output_size = resolve_max_output_size(max_output_size);
+    let raw = &dictionary.data;
+    let mut output = Vec::new();
+    py.allow_threads(|| {
+        decode_all(
+            "zstd",
+            payload,
+            &mut output,
+            max_output_size,
+            true,
+            false,
+            || ZstdStreamDecoder::with_prefix(raw),
+        )
+    })?;
+    Ok(PyBytes::new(py, &output).into())
+}
+
+/// Returns the registered dictionary whose hash the client advertised in
+/// `Available-Dictionary`, if any.
+#[pyfunction]
+pub(crate) fn match_available_dictionary(
+    py: Python<'_>,
+    available_dictionary: &PyAny,
+    dictionaries: Vec<Py<SharedDictionary>>,
+) -> PyResult<Option<Py<SharedDictionary>>> {
+    let value = to_bytes(available_dictionary)?;
+    let value = value.trim_ascii();
+    let Some(encoded) = value
+        .strip_prefix(b":")
+        .and_then(|value| value.strip_suffix(b":"))
+    else {
+        return Ok(None);
+    };
+    let Ok(hash) = STANDARD.decode(encoded) else {
+        return Ok(None);
+    };
+    Ok(dictionaries
+        .into_iter()
+        .find(|dictionary| dictionary.borrow(py).hash[..] == hash[..]))
+}
@@ -0,0 +1,520 @@
+use crate::encode::StreamEncoder;
+use crate::errors::{CodecError, InvalidParameterError};
+use crate::zstd_params::ZstdParams;
+use crate::{check_level, to_bytes};
+use flate2::Crc;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP64_END: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+const FLAG_UTF8: u16 = 0x0800;
+/// Upper byte 3 marks Unix attributes; the lower byte is the APPNOTE version (6.3).
+const VERSION_MADE_BY: u16 = 0x033f;
+const FILE_MODE: u32 = 0o100644;
+const DIRECTORY_MODE: u32 = 0o040755;
+
+#[derive(Clone, Copy)]
+enum Method {
+    Store,
+    Deflate,
+    Zstd,
+}
+
+impl Method {
+    fn parse(method: &str) -> PyResult<Self> {
+        match method {
+            "store" => Ok(Self::Store),
+            "deflate" => Ok(Self::Deflate),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(InvalidParameterError::new_err(format!(
+                "method must be 'store', 'deflate' or 'zstd', got {method:?}"
+            ))),
+        }
+    }
+
+    fn id(self) -> u16 {
+        match self {
+            Self::Store => 0,
+            Self::Deflate => 8,
+            Self::Zstd => 93,
+        }
+    }
+
+    fn version_needed(self, zip64: bool) -> u16 {
+        match self {
+            Self::Zstd => 63,
+            _ if zip64 => 45,
+            _ => 20,
+        }
+    }
+}
+
+/// Converts a Unix timestamp to MS-DOS `(time, date)`, clamped to 1980-2107 (UTC).
+fn dos_datetime(mtime: u64) -> (u16, u16) {
+    let days = (mtime / 86_400) as i64;
+    let seconds = mtime % 86_400;
+    // Civil-from-days conversion for the proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    if year > 2107 {
+        return ((23 << 11) | (59 << 5) | 29, (127 << 9) | (12 << 5) | 31);
+    }
+    let time = (seconds / 3600) << 11 | (seconds % 3600 / 60) << 5 | (seconds % 60 / 2);
+    let date = (year - 1980) << 9 | month << 5 | day;
+    (time as u16, date as u16)
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Returns `value` as a 32-bit field, or the `0xffffffff` marker that moves it into the
+/// ZIP64 extra field.
+fn field_u32(value: u64) -> Option<u32> {
+    u32::try_from(value).ok().filter(|value| *value != u32::MAX)
+}
+
+struct Entry {
+    name: Vec<u8>,
+    method: Method,
+    time: u16,
+    date: u16,
+    flags: u16,
+    offset: u64,
+    zip64: bool,
+    crc: u32,
+    compressed: u64,
+    uncompressed: u64,
+    mode: u32,
+}
+
+impl Entry {
+    fn central_header(&self, out: &mut Vec<u8>) {
+        let mut extra = Vec::new();
+        let uncompressed = field_u32(self.uncompressed);
+        let compressed = field_u32(self.compressed);
+        let offset = field_u32(self.offset);
+        if uncompressed.is_none() {
+            put_u64(&mut extra, self.uncompressed);
+        }
+        if compressed.is_none() {
+            put_u64(&mut extra, self.compressed);
+        }
+        if offset.is_none() {
+            put_u64(&mut extra, self.offset);
+        }
+        let zip64 = self.zip64 || !extra.is_empty();
+        put_u32(out, CENTRAL_HEADER);
+        put_u16(out, VERSION_MADE_BY);
+        put_u16(out, self.method.version_needed(zip64));
+        put_u16(out, self.flags);
+        put_u16(out, self.method.id());
+        put_u16(out, self.time);
+        put_u16(out, self.date);
+        put_u32(out, self.crc);
+        put_u32(out, compressed.unwrap_or(u32::MAX));
+        put_u32(out, uncompressed.unwrap_or(u32::MAX));
+        put_u16(out, self.name.len() as u16);
+        put_u16(
+            out,
+            if extra.is_empty() {
+                0
+            } else {
+                4 + extra.len() as u16
+            },
+        );
+        put_u16(out, 0);
+        put_u16(out, 0);
+        put_u16(out, 0);
+        put_u32(out, self.mode << 16);
+        put_u32(out, offset.unwrap_or(u32::MAX));
+        out.extend_from_slice(&self.name);
+        if !extra.is_empty() {
+            put_u16(out, ZIP64_EXTRA);
+            put_u16(out, extra.len() as u16);
+            out.extend_from_slice(&extra);
+        }
+    }
+}
+
+fn entry_too_large() -> PyErr {
+    PyValueError::new_err("entry exceeded 4 GiB; start it with zip64=True")
+}
+
+struct OpenEntry {
+    entry: Entry,
+    encoder: Option<StreamEncoder>,
+    crc: Crc,
+}
+
+/// Writes a ZIP archive front to back without seeking: every entry is followed by a data
+/// descriptor, and each method returns the bytes to send next, for example as the body of
+/// an ASGI `http.response.body` message. The central directory switches to ZIP64 records
+/// once offsets, sizes or the entry count outgrow the classic format.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct ZipStreamWriter {
+    offset: u64,
+    entries: Vec<Entry>,
+    current: Option<OpenEntry>,
+    finished: bool,
+    /// Set when an entry fails after some of it was emitted; the archive cannot be repaired.
+    failed: bool,
+    /// Size at which an entry needs ZIP64 records; tests lower it.
+    entry_limit: u64,
+}
+
+impl ZipStreamWriter {
+    fn ensure_open(&self) -> PyResult<()> {
+        if self.failed {
+            return Err(PyValueError::new_err(
+                "archive is unusable after an earlier error",
+            ));
+        }
+        if self.finished {
+            return Err(PyValueError::new_err("archive already finished"));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, chunk: Vec<u8>) -> Vec<u8> {
+        self.offset += chunk.len() as u64;
+        chunk
+    }
+
+    fn start(
+        &mut self,
+        name: &str,
+        method: &str,
+        level: Option<i32>,
+        mtime: Option<u64>,
+        zip64: bool,
+    ) -> PyResult<Vec<u8>> {
+        self.ensure_open()?;
+        if self.current.is_some() {
+            return Err(PyValueError::new_err("previous entry has not been ended"));
+        }
+        if name.is_empty() || name.len() > usize::from(u16::MAX) {
+            return Err(InvalidParameterError::new_err(
+                "entry name must be 1 to 65535 bytes",
+            ));
+        }
+        let is_directory = name.ends_with('/');
+        let method = if is_directory {
+            Method::Store
+        } else {
+            Method::parse(method)?
+        };
+        let encoder = match method {
+            Method::Store => None,
+            Method::Deflate => {
+                let level = level.unwrap_or(5);
+                check_level("deflate", level)?;
+                Some(StreamEncoder::deflate(level as u32, true))
+            }
+            Method::Zstd => {
+                let level = level.unwrap_or(3);
+                check_level("zstd", level)?;
+                Some(
+                    StreamEncoder::zstd(level, &ZstdParams::default())
+                        .map_err(|err| CodecError::encode(Some("zstd"), err))?,
+                )
+            }
+        };
+        let (time, date) = dos_datetime(mtime.unwrap_or(0));
+        let mut flags = FLAG_DATA_DESCRIPTOR;
+        if !name.is_ascii() {
+            flags |= FLAG_UTF8;
+        }
+        let entry = Entry {
+            name: name.as_bytes().to_vec(),
+            method,
+            time,
+            date,
+            flags,
+            offset: self.offset,
+            zip64,
+            crc: 0,
+            compressed: 0,
+            uncompressed: 0,
+            mode: if is_directory {
+                DIRECTORY_MODE
+            } else {
+                FILE_MODE
+            },
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        put_u32(&mut header, LOCAL_HEADER);
+        put_u16(&mut header, method.version_needed(zip64));
+        put_u16(&mut header, flags);
+        put_u16(&mut header, method.id());
+        put_u16(&mut header, time);
+        put_u16(&mut header, date);
+        put_u32(&mut header, 0);
+        // Sizes follow in the data descriptor; the ZIP64 marker says they will be 8 bytes.
+        let size_marker = if zip64 { u32::MAX } else { 0 };
+        put_u32(&mut header, size_marker);
+        put_u32(&mut header, size_marker);
+        put_u16(&mut header, name.len() as u16);
+        put_u16(&mut header, if zip64 { 20 } else { 0 });
+        header.extend_from_slice(name.as_bytes());
+        if zip64 {
+            put_u16(&mut header, ZIP64_EXTRA);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, 0);
+            put_u64(&mut header, 0);
+        }
+        self.current = Some(OpenEntry {
+            entry,
+            encoder,
+            crc: Crc::new(),
+        });
+        Ok(self.emit(header))
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> PyResult<Vec<u8>> {
+        self.ensure_open()?;
+        let current = self
+            .current
+            .as_mut()
+            .ok_or_else(|| PyValueError::new_err("no entry has been started"))?;
+        current.crc.update(data);
+        current.entry.uncompressed += data.len() as u64;
+        let chunk = match &mut current.encoder {
+            Some(encoder) => encoder.update(data).map_err(|err| {
+                self.failed = true;
+                CodecError::encode(Some(encoder.coding()), err)
+            })?,
+            None => data.to_vec(),
+        };
+        // Refuse before emitting anything that would take the entry past the classic limit.
+        let compressed = current.entry.compressed + chunk.len() as u64;
+        if !current.entry.zip64 && compressed.max(current.entry.uncompressed) >= self.entry_limit {
+            self.failed = true;
+            return Err(entry_too_large());
+        }
+        current.entry.compressed = compressed;
+        Ok(self.emit(chunk))
+    }
+
+    fn end(&mut self) -> PyResult<Vec<u8>> {
+        self.ensure_open()?;
+        let OpenEntry {
+            mut entry,
+            encoder,
+            crc,
+        } = self
+            .current
+            .take()
+            .ok_or_else(|| PyValueError::new_err("no entry has been started"))?;
+        let mut chunk = match encoder {
+            Some(encoder) => {
+                let coding = encoder.coding();
+                encoder.finish().map_err(|err| {
+                    self.failed = true;
+                    CodecError::encode(Some(coding), err)
+                })?
+            }
+            None => Vec::new(),
+        };
+        entry.compressed += chunk.len() as u64;
+        entry.crc = crc.sum();
+        let too_large = entry.compressed.max(entry.uncompressed) >= self.entry_limit;
+        if too_large && !entry.zip64 {
+            self.failed = true;
+            return Err(entry_too_large());
+        }
+        put_u32(&mut chunk, DATA_DESCRIPTOR);
+        put_u32(&mut chunk, entry.crc);
+        if entry.zip64 {
+            put_u64(&mut chunk, entry.compressed);
+            put_u64(&mut chunk, entry.uncompressed);
+        } else {
+            put_u32(&mut chunk, entry.compressed as u32);
+            put_u32(&mut chunk, entry.uncompressed as u32);
+        }
+        self.entries.push(entry);
+        Ok(self.emit(chunk))
+    }
+}
+
+#[pymethods]
+impl ZipStreamWriter {
+    #[new]
+    fn new() -> Self {
+        Self {
+            offset: 0,
+            entries: Vec::new(),
+            current: None,
+            finished: false,
+            failed: false,
+            entry_limit: u64::from(u32::MAX),
+        }
+    }
+
+    /// Opens an entry and returns its local header. Entries streamed past 4 GiB need
+    /// `zip64=True` up front, since the header is sent before the size is known.
+    #[pyo3(signature = (name, *, method="deflate", level=None, mtime=None, zip64=false))]
+    fn start_entry<'py>(
+        &mut self,
+        py: Python<'py>,
+        name: &str,
+        method: &str,
+        level: Option<i32>,
+        mtime: Option<u64>,
+        zip64: bool,
+    ) -> PyResult<&'py PyBytes> {
+        let chunk = self.start(name, method, level, mtime, zip64)?;
+        Ok(PyBytes::new(py, &chunk))
+    }
+
+    /// Adds data to the open entry and returns whatever compressed bytes are ready.
+    fn write<'py>(&mut self, py: Python<'py>, data: &PyAny) -> PyResult<&'py PyBytes> {
+        let chunk = self.write_data(&to_bytes(data)?)?;
+        Ok(PyBytes::new(py, &chunk))
+    }
+
+    /// Closes the open entry, returning the rest of its data and the data descriptor.
+    fn end_entry<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let chunk = self.end()?;
+        Ok(PyBytes::new(py, &chunk))
+    }
+
+    /// Writes a whole entry at once, choosing ZIP64 from the known size.
+    #[pyo3(signature = (name, data, *, method="deflate", level=None, mtime=None))]
+    fn add<'py>(
+        &mut self,
+        py: Python<'py>,
+        name: &str,
+        data: &PyAny,
+        method: &str,
+        level: Option<i32>,
+        mtime: Option<u64>,
+    ) -> PyResult<&'py PyBytes> {
+        let data = to_bytes(data)?;
+        // Compressed output can slightly exceed the input, so leave headroom.
+        let zip64 = data.len() as u64 >= u64::from(u32::MAX) - (1 << 20);
+        let mut chunk = self.start(name, method, level, mtime, zip64)?;
+        chunk.extend(self.write_data(&data)?);
+        chunk.extend(self.end()?);
+        Ok(PyBytes::new(py, &chunk))
+    }
+
+    /// Writes the central directory and end records. No entries can be added afterwards.
+    fn finish<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        self.ensure_open()?;
+        if self.current.is_some() {
+            return Err(PyValueError::new_err("last entry has not been ended"));
+        }
+        let directory_offset = self.offset;
+        let mut out = Vec::new();
+        for entry in &self.entries {
+            entry.central_header(&mut out);
+        }
+        let directory_size = out.len() as u64;
+        let count = self.entries.len() as u64;
+        let count16 = u16::try_from(count).ok().filter(|count| *count != u16::MAX);
+        let size32 = field_u32(directory_size);
+        let offset32 = field_u32(directory_offset);
+        if count16.is_none() || size32.is_none() || offset32.is_none() {
+            let end_offset = directory_offset + directory_size;
+            put_u32(&mut out, ZIP64_END);
+            put_u64(&mut out, 44);
+            put_u16(&mut out, VERSION_MADE_BY);
+            put_u16(&mut out, 45);
+            put_u32(&mut out, 0);
+            put_u32(&mut out, 0);
+            put_u64(&mut out, count);
+            put_u64(&mut out, count);
+            put_u64(&mut out, directory_size);
+            put_u64(&mut out, directory_offset);
+            put_u32(&mut out, ZIP64_LOCATOR);
+            put_u32(&mut out, 0);
+            put_u64(&mut out, end_offset);
+            put_u32(&mut out, 1);
+        }
+        put_u32(&mut out, END_OF_CENTRAL_DIRECTORY);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+        put_u16(&mut out, count16.unwrap_or(u16::MAX));
+        put_u16(&mut out, count16.unwrap_or(u16::MAX));
+        put_u32(&mut out, size32.unwrap_or(u32::MAX));
+        put_u32(&mut out, offset32.unwrap_or(u32::MAX));
+        put_u16(&mut out, 0);
+        self.finished = true;
+        let out = self.emit(out);
+        Ok(PyBytes::new(py, &out))
+    }
+
+    /// Bytes emitted so far.
+    #[getter]
+    fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+// Linking these needs libpython, so run them with `cargo test --no-default-features`.
+#[cfg(all(test, not(feature = "extension-module")))]
+mod tests {
+    use super::*;
+
+    fn small_writer() -> ZipStreamWriter {
+        ZipStreamWriter {
+            entry_limit: 1000,
+            ..ZipStreamWriter::new()
+        }
+    }
+
+    #[test]
+    fn oversized_entry_fails_before_its_bytes_are_emitted() {
+        let mut writer = small_writer();
+        assert!(writer.start("huge.bin", "store", None, None, false).is_ok());
+        assert_eq!(
+            writer.write_data(&[0; 600]).ok().map(|chunk| chunk.len()),
+            Some(600)
+        );
+        let offset = writer.offset;
+        assert!(writer.write_data(&[0; 600]).is_err());
+        assert_eq!(writer.offset, offset);
+        assert!(writer.end().is_err());
+        assert!(writer
+            .start("next.txt", "store", None, None, false)
+            .is_err());
+    }
+
+    #[test]
+    fn zip64_entry_may_grow_past_the_classic_limit() {
+        let mut writer = small_writer();
+        assert!(writer.start("huge.bin", "store", None, None, true).is_ok());
+        assert!(writer.write_data(&[0; 2000]).is_ok());
+        assert!(writer.end().is_ok());
+        assert_eq!(writer.entries[0].uncompressed, 2000);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::errors::{CodecError, InvalidParameterError};
+use crate::to_bytes;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use zstd::dict::{CDict, DDict, DecoderDictionary, EncoderDictionary};
+
+/// A zstd dictionary, prepared once for decoding and once per compression level for
+/// encoding. Raw bytes without the zstd dictionary magic are used as a raw-content
+/// dictionary.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct ZstdDictionary {
+    data: Vec<u8>,
+    decoder: Arc<DecoderDictionary<'static>>,
+    encoders: Mutex<HashMap<i32, Arc<EncoderDictionary<'static>>>>,
+}
+
+impl ZstdDictionary {
+    /// Raises `InvalidParameterError` for bytes that carry the zstd dictionary magic but do
+    /// not parse as a dictionary; the prepared dictionaries would otherwise fail to build.
+    pub fn from_vec(data: Vec<u8>) -> PyResult<Self> {
+        if DDict::try_create(&data).is_none() || CDict::try_create(&data, 3).is_none() {
+            return Err(InvalidParameterError::new_err("malformed zstd dictionary"));
+        }
+        let decoder = Arc::new(DecoderDictionary::copy(&data));
+        Ok(Self {
+            data,
+            decoder,
+            encoders: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn decoder(&self) -> Arc<DecoderDictionary<'static>> {
+        Arc::clone(&self.decoder)
+    }
+
+    pub fn encoder(&self, level: i32) -> Arc<EncoderDictionary<'static>> {
+        let mut encoders = self.encoders.lock().unwrap_or_else(|err| err.into_inner());
+        let encoder = encoders
+            .entry(level)
+            .or_insert_with(|| Arc::new(EncoderDictionary::copy(&self.data, level)));
+        Arc::clone(encoder)
+    }
+}
+
+#[pymethods]
+impl ZstdDictionary {
+    #[new]
+    fn new(data: &PyAny) -> PyResult<Self> {
+        Self::from_vec(to_bytes(data)?.into_owned())
+    }
+
+    /// Dictionary ID from the zstd dictionary header, or `None` for raw-content dictionaries.
+    #[getter]
+    fn dict_id(&self) -> Option<u32> {
+        zstd::zstd_safe::get_dict_id_from_dict(&self.data).map(u32::from)
+    }
+
+    fn __bytes__<'py>(&self, py: Python<'py>) -> &'py PyBytes {
+        PyBytes::new(py, &self.data)
+    }
+
+    fn __len__(&self) -> usize {
+        self.data.len()
+    }
+}
+
+#[pyfunction]
+pub(crate) fn zstd_train_dictionary(
+    py: Python<'_>,
+    samples: Vec<&PyAny>,
+    size: usize,
+) -> PyResult<ZstdDictionary> {
+    let samples = samples
+        .into_iter()
+        .map(to_bytes)
+        .collect::<PyResult<Vec<_>>>()?;
+    let data = py
+        .allow_threads(|| zstd::dict::from_samples(&samples, size))
+        .map_err(|err| CodecError::encode(Some("zstd"), err))?;
+    ZstdDictionary::from_vec(data)
+}
@@ -0,0 +1,150 @@
+use crate::errors::CodecError;
+use crate::to_bytes;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use zstd::zstd_safe;
+
+const ZSTD_MAGIC: u32 = 0xfd2f_b528;
+/// Skippable frames use the 16 magic numbers 0x184D2A50 to 0x184D2A5F.
+const SKIPPABLE_MAGIC_MASK: u32 = 0xffff_fff0;
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+
+/// Frame header fields of one zstd frame (RFC 8878, section 3.1.1). The declared sizes come
+/// from the sender, so check them against your own limits before trusting them.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct ZstdFrameInfo {
+    /// Position of the frame within the data it was read from.
+    #[pyo3(get)]
+    offset: usize,
+    /// Length of the whole frame, or `None` when `data` ends before the frame does.
+    #[pyo3(get)]
+    compressed_size: Option<usize>,
+    #[pyo3(get)]
+    header_size: usize,
+    #[pyo3(get)]
+    skippable: bool,
+    #[pyo3(get)]
+    content_size: Option<u64>,
+    #[pyo3(get)]
+    window_size: u64,
+    #[pyo3(get)]
+    dictionary_id: Option<u32>,
+    #[pyo3(get)]
+    has_checksum: bool,
+}
+
+/// Parses the frame starting at `data[offset..]`.
+fn frame_info(data: &[u8], offset: usize) -> PyResult<ZstdFrameInfo> {
+    let frame = &data[offset..];
+    let truncated = || PyErr::from(CodecError::truncated("zstd", data.len(), Vec::new()));
+    let magic = frame
+        .get(..4)
+        .map(|magic| u32::from_le_bytes([magic[0], magic[1], magic[2], magic[3]]))
+        .ok_or_else(truncated)?;
+    if magic & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+        let size = frame.get(4..8).ok_or_else(truncated)?;
+        let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize + 8;
+        return Ok(ZstdFrameInfo {
+            offset,
+            compressed_size: (frame.len() >= size).then_some(size),
+            header_size: 8,
+            skippable: true,
+            content_size: None,
+            window_size: 0,
+            dictionary_id: None,
+            has_checksum: false,
+        });
+    }
+    if magic != ZSTD_MAGIC {
+        return Err(CodecError::corrupt(Some("zstd"), None, "not a zstd frame").into());
+    }
+    let descriptor = *frame.get(4).ok_or_else(truncated)?;
+    if descriptor & 0x08 != 0 {
+        return Err(CodecError::corrupt(
+            Some("zstd"),
+            None,
+            "reserved bit set in zstd frame header",
+        )
+        .into());
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    let content_size_len = match descriptor >> 6 {
+        0 => usize::from(single_segment),
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let dictionary_id_len = [0, 1, 2, 4][usize::from(descriptor & 0x03)];
+    let header_size = 5 + usize::from(!single_segment) + dictionary_id_len + content_size_len;
+    if frame.len() < header_size {
+        return Err(truncated());
+    }
+    let content_size = zstd_safe::get_frame_content_size(frame)
+        .map_err(|_| CodecError::corrupt(Some("zstd"), None, "invalid zstd frame header"))?;
+    let window_size = if single_segment {
+        content_size.unwrap_or_default()
+    } else {
+        let exponent = u32::from(frame[5] >> 3);
+        let mantissa = u64::from(frame[5] & 0x07);
+        let base = 1u64 << (10 + exponent);
+        base + base / 8 * mantissa
+    };
+    let compressed_size = zstd_safe::find_frame_compressed_size(frame).ok();
+    Ok(ZstdFrameInfo {
+        offset,
+        compressed_size,
+        header_size,
+        skippable: false,
+        content_size,
+        window_size,
+        dictionary_id: zstd_safe::get_dict_id_from_frame(frame).map(|id| id.get()),
+        has_checksum: descriptor & 0x04 != 0,
+    })
+}
+
+/// Reads the header of the first frame in `data`, which may be just a prefix of the frame.
+#[pyfunction]
+pub(crate) fn zstd_frame_info(data: &PyAny) -> PyResult<ZstdFrameInfo> {
+    frame_info(&to_bytes(data)?, 0)
+}
+
+/// Yields a `ZstdFrameInfo` per frame of concatenated zstd data, skippable frames included;
+/// `data[info.offset:info.offset + info.compressed_size]` is the frame itself.
+#[pyclass(module = "fastcomp")]
+pub(crate) struct ZstdFrameIterator {
+    data: Py<PyBytes>,
+    offset: usize,
+}
+
+#[pymethods]
+impl ZstdFrameIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<ZstdFrameInfo>> {
+        let data = self.data.as_ref(py).as_bytes();
+        if self.offset == data.len() {
+            return Ok(None);
+        }
+        let info = frame_info(data, self.offset)?;
+        let size = info.compressed_size.ok_or_else(|| {
+            CodecError::corrupt(
+                Some("zstd"),
+                Some(self.offset),
+                "truncated or corrupt zstd frame",
+            )
+        })?;
+        self.offset += size;
+        Ok(Some(info))
+    }
+}
+
+#[pyfunction]
+pub(crate) fn iter_zstd_frames(py: Python<'_>, data: &PyAny) -> PyResult<ZstdFrameIterator> {
+    let data = match data.downcast::<PyBytes>() {
+        Ok(data) => data.into(),
+        Err(_) => PyBytes::new(py, &to_bytes(data)?).into(),
+    };
+    Ok(ZstdFrameIterator { data, offset: 0 })
+}
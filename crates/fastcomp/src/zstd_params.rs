@@ -0,0 +1,93 @@
+use crate::errors::InvalidParameterError;
+use pyo3::prelude::*;
+use std::io::{self, Write};
+use zstd::stream::write::Encoder;
+
+/// Advanced zstd encoder settings shared by `zstd_compress` and `ZstdCompressor`.
+/// `window_log` stops at 27, the largest window zstd decoders accept by default, so every
+/// frame written here also decodes here.
+#[pyclass(module = "fastcomp")]
+#[derive(Clone)]
+pub(crate) struct ZstdParams {
+    #[pyo3(get)]
+    workers: u32,
+    #[pyo3(get)]
+    window_log: Option<u32>,
+    #[pyo3(get)]
+    long_distance_matching: bool,
+    #[pyo3(get)]
+    checksum: bool,
+    #[pyo3(get)]
+    content_size: bool,
+}
+
+impl Default for ZstdParams {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            window_log: None,
+            long_distance_matching: false,
+            checksum: false,
+            content_size: true,
+        }
+    }
+}
+
+impl ZstdParams {
+    /// Applies the settings to a fresh encoder. `size` is pledged as the frame content size
+    /// when known and `content_size` is set.
+    pub fn apply<W: Write>(
+        &self,
+        encoder: &mut Encoder<'_, W>,
+        size: Option<usize>,
+    ) -> io::Result<()> {
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+        if let Some(window_log) = self.window_log {
+            encoder.window_log(window_log)?;
+        }
+        if self.long_distance_matching {
+            encoder.long_distance_matching(true)?;
+        }
+        encoder.include_checksum(self.checksum)?;
+        encoder.include_contentsize(self.content_size)?;
+        if self.content_size {
+            encoder.set_pledged_src_size(size.map(|size| size as u64))?;
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl ZstdParams {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        workers=0,
+        window_log=None,
+        long_distance_matching=false,
+        checksum=false,
+        content_size=true,
+    ))]
+    fn new(
+        workers: u32,
+        window_log: Option<u32>,
+        long_distance_matching: bool,
+        checksum: bool,
+        content_size: bool,
+    ) -> PyResult<Self> {
+        if window_log.is_some_and(|window_log| !(10..=27).contains(&window_log)) {
+            return Err(InvalidParameterError::new_err(
+                "window_log must be between 10 and 27",
+            ));
+        }
+        Ok(Self {
+            workers,
+            window_log,
+            long_distance_matching,
+            checksum,
+            content_size,
+        })
+    }
+}
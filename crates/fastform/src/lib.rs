@@ -1,19 +1,22 @@
 use memchr::memmem::Finder;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::{PyByteArray, PyBytes, PyDict};
+use pyo3::types::{PyByteArray, PyBytes, PyDict, PyMemoryView};
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-fn read_bytes<'py>(obj: &'py PyAny) -> PyResult<&'py [u8]> {
+fn read_bytes(obj: &PyAny) -> PyResult<Cow<'_, [u8]>> {
     if let Ok(bytes) = obj.downcast::<PyBytes>() {
-        return Ok(bytes.as_bytes());
+        return Ok(Cow::Borrowed(bytes.as_bytes()));
     }
     if let Ok(ba) = obj.downcast::<PyByteArray>() {
-        unsafe {
-            return Ok(ba.as_bytes());
-        }
+        // Copied, since Python code run while parsing could resize it under the slice.
+        return Ok(Cow::Owned(ba.to_vec()));
     }
-    Err(PyValueError::new_err("expected bytes-like object"))
+    let view =
+        PyMemoryView::from(obj).map_err(|_| PyValueError::new_err("expected bytes-like object"))?;
+    let bytes = view.call_method0("tobytes")?.downcast::<PyBytes>()?;
+    Ok(Cow::Borrowed(bytes.as_bytes()))
 }
 
 fn percent_decode(input: &[u8]) -> Vec<u8> {
@@ -75,7 +78,7 @@ struct Part {
 
 fn read_stream(_py: Python<'_>, stream: &PyAny) -> PyResult<Vec<u8>> {
     if let Ok(bytes) = read_bytes(stream) {
-        return Ok(bytes.to_vec());
+        return Ok(bytes.into_owned());
     }
     if stream.hasattr("read")? {
         let mut buffer = Vec::new();
@@ -84,21 +87,12 @@ fn read_stream(_py: Python<'_>, stream: &PyAny) -> PyResult<Vec<u8>> {
             if chunk.is_none() {
                 break;
             }
-            if let Ok(bytes) = chunk.downcast::<PyBytes>() {
-                let data = bytes.as_bytes();
-                if data.is_empty() {
-                    break;
-                }
-                buffer.extend_from_slice(data);
-            } else if let Ok(bytearray) = chunk.downcast::<PyByteArray>() {
-                let data = unsafe { bytearray.as_bytes() };
-                if data.is_empty() {
-                    break;
-                }
-                buffer.extend_from_slice(data);
-            } else {
-                return Err(PyValueError::new_err("read() must return bytes"));
+            let data =
+                read_bytes(chunk).map_err(|_| PyValueError::new_err("read() must return bytes"))?;
+            if data.is_empty() {
+                break;
             }
+            buffer.extend_from_slice(&data);
         }
         return Ok(buffer);
     }
@@ -202,7 +196,7 @@ fn parse_multipart_impl<'py>(py: Python<'py>, body: &[u8], boundary: &[u8]) -> P
 fn parse_multipart(py: Python<'_>, stream: &PyAny, boundary: &PyAny) -> PyResult<Vec<Part>> {
     let boundary = read_bytes(boundary)?;
     let body = read_stream(py, stream)?;
-    parse_multipart_impl(py, &body, boundary)
+    parse_multipart_impl(py, &body, &boundary)
 }
 
 #[pymodule]
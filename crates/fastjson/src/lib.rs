@@ -1,21 +1,25 @@
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::{
-    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple,
+    PyBool, PyByteArray, PyBytes, PyDict, PyFloat, PyInt, PyList, PyMemoryView, PyString, PyTuple,
 };
 use pyo3::wrap_pyfunction;
 use serde::Serialize;
 use serde_json::ser::{CompactFormatter, PrettyFormatter, Serializer};
 use serde_json::{Map, Value};
+use std::borrow::Cow;
 
-fn read_bytes_like<'py>(_py: Python<'py>, obj: &'py PyAny) -> PyResult<&'py [u8]> {
+fn read_bytes_like(obj: &PyAny) -> PyResult<Cow<'_, [u8]>> {
     if let Ok(bytes) = obj.downcast::<PyBytes>() {
-        return Ok(bytes.as_bytes());
+        return Ok(Cow::Borrowed(bytes.as_bytes()));
     }
     if let Ok(bytearray) = obj.downcast::<PyByteArray>() {
-        return Ok(unsafe { bytearray.as_bytes() });
+        return Ok(Cow::Owned(bytearray.to_vec()));
     }
-    Err(PyValueError::new_err("expected bytes-like object"))
+    let view =
+        PyMemoryView::from(obj).map_err(|_| PyValueError::new_err("expected bytes-like object"))?;
+    let bytes = view.call_method0("tobytes")?.downcast::<PyBytes>()?;
+    Ok(Cow::Borrowed(bytes.as_bytes()))
 }
 
 fn serialize_value(value: &Value, indent: Option<usize>) -> PyResult<Vec<u8>> {
@@ -134,18 +138,18 @@ fn value_to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
 #[pyfunction]
 #[pyo3(text_signature = "(data, /, *, allow_nan=False)")]
 fn loads(py: Python<'_>, data: &PyAny, allow_nan: bool) -> PyResult<PyObject> {
-    let bytes = read_bytes_like(py, data)?;
+    let bytes = read_bytes_like(data)?;
     if allow_nan {
         let json = py.import("json")?;
         let kwargs = PyDict::new(py);
         kwargs.set_item("allow_nan", true)?;
         let text =
-            std::str::from_utf8(bytes).map_err(|err| PyValueError::new_err(err.to_string()))?;
+            std::str::from_utf8(&bytes).map_err(|err| PyValueError::new_err(err.to_string()))?;
         let result = json.call_method("loads", (PyString::new(py, text),), Some(kwargs))?;
         return Ok(result.into());
     }
     let value: Value =
-        serde_json::from_slice(bytes).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        serde_json::from_slice(&bytes).map_err(|err| PyValueError::new_err(err.to_string()))?;
     value_to_py(py, &value)
 }
 
@@ -30,10 +30,18 @@ async def app(scope, receive, send):  # type: ignore[override]
     route_id, _params = router.lookup(scope["method"], scope.get("headers", {}).get(b"host"), scope["path"])
     if route_id == 1:
         payload = fastjson.dumps({"message": "hello from FastIO"})
-        body = fastcomp.gzip_compress(payload)
+        headers = [(b"content-type", b"application/json"), (b"vary", b"accept-encoding")]
+        accept_encoding = dict(scope.get("headers", [])).get(b"accept-encoding", b"")
+        negotiated = fastcomp.negotiate_compressor(accept_encoding)
+        if negotiated is None:
+            body = payload
+        else:
+            coding, compressor = negotiated
+            body = compressor.update(payload) + compressor.finish()
+            headers.append((b"content-encoding", coding.encode()))
         await send({
             "type": "http.response.start",
             "status": 200,
-            "headers": [(b"content-type", b"application/json"), (b"content-encoding", b"gzip")],
+            "headers": headers,
         })
         await send({"type": "http.response.body", "body": body})
@@ -4,24 +4,134 @@ from __future__ import annotations
 
 from ._fastcomp import (
     BrotliCompressor,
+    BrotliDecompressor,
+    BrotliParams,
+    BufferTooSmall,
+    CompressionError,
+    CompressionPolicy,
+    CompressionResponder,
+    CorruptDataError,
+    DecompressionLimitExceeded,
+    DeflateCompressor,
+    DeflateDecompressor,
     GzipCompressor,
+    GzipDecompressor,
+    GzipInfo,
+    InvalidParameterError,
+    Lz4Compressor,
+    Lz4Decompressor,
+    PerMessageDeflate,
+    PrecompressedStore,
+    SeekableZstdReader,
+    SharedDictionary,
+    SnappyCompressor,
+    SnappyDecompressor,
+    TruncatedInputError,
+    ZipStreamWriter,
     ZstdCompressor,
+    ZstdDecompressor,
+    ZstdDictionary,
+    ZstdFrameInfo,
+    ZstdFrameIterator,
+    ZstdParams,
+    ZstdSeekableCompressor,
+    acompress,
+    adecompress,
     brotli_compress,
     brotli_decompress,
+    compress_adaptive,
+    compress_into,
+    dcb_compress,
+    dcb_decompress,
+    dcz_compress,
+    dcz_decompress,
+    decompress,
+    decompress_into,
+    deflate_compress,
+    deflate_decompress,
+    get_default_max_output_size,
     gzip_compress,
     gzip_decompress,
+    gzip_info,
+    iter_zstd_frames,
+    lz4_compress,
+    lz4_decompress,
+    match_available_dictionary,
+    negotiate,
+    negotiate_compressor,
+    set_default_max_output_size,
+    snappy_compress,
+    snappy_decompress,
     zstd_compress,
     zstd_decompress,
+    zstd_frame_info,
+    zstd_seekable_compress,
+    zstd_train_dictionary,
 )
 
 __all__ = [
     "BrotliCompressor",
+    "BrotliDecompressor",
+    "BrotliParams",
+    "BufferTooSmall",
+    "CompressionError",
+    "CompressionPolicy",
+    "CompressionResponder",
+    "CorruptDataError",
+    "DecompressionLimitExceeded",
+    "DeflateCompressor",
+    "DeflateDecompressor",
     "GzipCompressor",
+    "GzipDecompressor",
+    "GzipInfo",
+    "InvalidParameterError",
+    "Lz4Compressor",
+    "Lz4Decompressor",
+    "PerMessageDeflate",
+    "PrecompressedStore",
+    "SeekableZstdReader",
+    "SharedDictionary",
+    "SnappyCompressor",
+    "SnappyDecompressor",
+    "TruncatedInputError",
+    "ZipStreamWriter",
     "ZstdCompressor",
+    "ZstdDecompressor",
+    "ZstdDictionary",
+    "ZstdFrameInfo",
+    "ZstdFrameIterator",
+    "ZstdParams",
+    "ZstdSeekableCompressor",
+    "acompress",
+    "adecompress",
     "brotli_compress",
     "brotli_decompress",
+    "compress_adaptive",
+    "compress_into",
+    "dcb_compress",
+    "dcb_decompress",
+    "dcz_compress",
+    "dcz_decompress",
+    "decompress",
+    "decompress_into",
+    "deflate_compress",
+    "deflate_decompress",
+    "get_default_max_output_size",
     "gzip_compress",
     "gzip_decompress",
+    "gzip_info",
+    "iter_zstd_frames",
+    "lz4_compress",
+    "lz4_decompress",
+    "match_available_dictionary",
+    "negotiate",
+    "negotiate_compressor",
+    "set_default_max_output_size",
+    "snappy_compress",
+    "snappy_decompress",
     "zstd_compress",
     "zstd_decompress",
+    "zstd_frame_info",
+    "zstd_seekable_compress",
+    "zstd_train_dictionary",
 ]
@@ -1,34 +1,475 @@
-from typing import Optional, Protocol
+from collections.abc import Mapping, Sequence
+from typing import Any, BinaryIO, Literal, Optional, Protocol
 
 BytesLike = bytes | bytearray | memoryview
 
+class CompressionError(ValueError):
+    """Base of every fastcomp error; `consumed` counts input bytes read before the failure."""
+
+    codec: Optional[str]
+    consumed: Optional[int]
+
+class TruncatedInputError(CompressionError):
+    """The input ended mid-stream; `partial_output` holds everything decoded until then."""
+
+    partial_output: bytes
+
+class CorruptDataError(CompressionError): ...
+class InvalidParameterError(CompressionError): ...
+class DecompressionLimitExceeded(CompressionError): ...
+
+class BufferTooSmall(CompressionError):
+    """Raised by the `*_into` functions; `args[1]` is the number of bytes required."""
+
 class _Compressor(Protocol):
     def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
     def finish(self) -> bytes: ...
 
 class GzipCompressor:
-    def __init__(self, level: int = ...) -> None: ...
+    def __init__(
+        self,
+        level: int = ...,
+        *,
+        filename: Optional[str] = ...,
+        mtime: Optional[int] = ...,
+        comment: Optional[str] = ...,
+    ) -> None: ...
+    def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
+    def finish(self) -> bytes: ...
+
+class DeflateCompressor:
+    def __init__(self, level: int = ..., *, raw: bool = ...) -> None: ...
     def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
     def finish(self) -> bytes: ...
 
+class BrotliParams:
+    def __init__(
+        self,
+        *,
+        lgwin: int = ...,
+        lgblock: Optional[int] = ...,
+        mode: Literal["generic", "text", "font"] = ...,
+        size_hint: int = ...,
+    ) -> None: ...
+    @property
+    def lgwin(self) -> int: ...
+    @property
+    def lgblock(self) -> Optional[int]: ...
+    @property
+    def mode(self) -> str: ...
+    @property
+    def size_hint(self) -> int: ...
+
 class BrotliCompressor:
-    def __init__(self, level: int = ...) -> None: ...
+    def __init__(self, level: int = ..., *, params: Optional[BrotliParams] = ...) -> None: ...
     def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
     def finish(self) -> bytes: ...
 
+class ZstdDictionary:
+    def __init__(self, data: BytesLike) -> None: ...
+    @property
+    def dict_id(self) -> Optional[int]: ...
+    def __bytes__(self) -> bytes: ...
+    def __len__(self) -> int: ...
+
+class SharedDictionary:
+    def __init__(self, data: BytesLike) -> None: ...
+    @property
+    def hash(self) -> bytes: ...
+    @property
+    def available_dictionary(self) -> bytes: ...
+    def __bytes__(self) -> bytes: ...
+    def __len__(self) -> int: ...
+
+class ZstdParams:
+    def __init__(
+        self,
+        *,
+        workers: int = ...,
+        window_log: Optional[int] = ...,
+        long_distance_matching: bool = ...,
+        checksum: bool = ...,
+        content_size: bool = ...,
+    ) -> None: ...
+    @property
+    def workers(self) -> int: ...
+    @property
+    def window_log(self) -> Optional[int]: ...
+    @property
+    def long_distance_matching(self) -> bool: ...
+    @property
+    def checksum(self) -> bool: ...
+    @property
+    def content_size(self) -> bool: ...
+
 class ZstdCompressor:
-    def __init__(self, level: int = ...) -> None: ...
+    def __init__(
+        self,
+        level: int = ...,
+        *,
+        dictionary: Optional[ZstdDictionary] = ...,
+        params: Optional[ZstdParams] = ...,
+    ) -> None: ...
     def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
     def finish(self) -> bytes: ...
 
-def gzip_compress(data: BytesLike, level: int = ...) -> bytes: ...
+class ZstdSeekableCompressor:
+    def __init__(
+        self, level: int = ..., *, frame_size: int = ..., checksum: bool = ...
+    ) -> None: ...
+    def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
+    def finish(self) -> bytes: ...
+
+class Lz4Compressor:
+    def __init__(self) -> None: ...
+    def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
+    def finish(self) -> bytes: ...
+
+class SnappyCompressor:
+    def __init__(self) -> None: ...
+    def update(self, data: BytesLike) -> bytes: ...
+    def flush(self, mode: Literal["sync", "full"] = ...) -> bytes: ...
+    def finish(self) -> bytes: ...
+
+class GzipDecompressor:
+    def __init__(self, *, max_output_size: Optional[int] = ...) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+class DeflateDecompressor:
+    def __init__(self, *, max_output_size: Optional[int] = ...) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+class BrotliDecompressor:
+    def __init__(self, *, max_output_size: Optional[int] = ...) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+class ZstdDecompressor:
+    def __init__(
+        self,
+        *,
+        max_output_size: Optional[int] = ...,
+        dictionary: Optional[ZstdDictionary] = ...,
+    ) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+class Lz4Decompressor:
+    def __init__(self, *, max_output_size: Optional[int] = ...) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+class SnappyDecompressor:
+    def __init__(self, *, max_output_size: Optional[int] = ...) -> None: ...
+    def decompress(self, data: BytesLike, max_length: int = ...) -> bytes: ...
+    @property
+    def eof(self) -> bool: ...
+    @property
+    def unused_data(self) -> bytes: ...
+    @property
+    def needs_input(self) -> bool: ...
+
+_Decision = Literal["always", "never", "auto"]
+
+class CompressionPolicy:
+    def __init__(
+        self,
+        rules: Optional[Mapping[str, _Decision]] = ...,
+        *,
+        default: _Decision = ...,
+        max_entropy: float = ...,
+        sample_size: int = ...,
+    ) -> None: ...
+    @property
+    def max_entropy(self) -> float: ...
+    @property
+    def sample_size(self) -> int: ...
+    def decide(self, content_type: Optional[str | BytesLike] = ...) -> _Decision: ...
+    def should_compress(
+        self, data: BytesLike, content_type: Optional[str | BytesLike] = ...
+    ) -> bool: ...
+
+class PerMessageDeflate:
+    def __init__(
+        self,
+        *,
+        is_server: bool = ...,
+        level: int = ...,
+        server_no_context_takeover: bool = ...,
+        client_no_context_takeover: bool = ...,
+        server_max_window_bits: Optional[int] = ...,
+        client_max_window_bits: Optional[int] = ...,
+        max_output_size: Optional[int] = ...,
+    ) -> None: ...
+    @property
+    def enabled(self) -> bool: ...
+    @property
+    def server_no_context_takeover(self) -> bool: ...
+    @property
+    def client_no_context_takeover(self) -> bool: ...
+    @property
+    def server_max_window_bits(self) -> int: ...
+    @property
+    def client_max_window_bits(self) -> int: ...
+    def negotiate(self, header: str | BytesLike) -> Optional[str]: ...
+    def offer(self) -> str: ...
+    def accept(self, header: str | BytesLike) -> None: ...
+    def compress(self, data: BytesLike) -> bytes: ...
+    def decompress(self, data: BytesLike) -> bytes: ...
+
+class PrecompressedStore:
+    def __init__(
+        self,
+        max_bytes: int,
+        *,
+        codings: Optional[Sequence[str]] = ...,
+        levels: Optional[Mapping[str, int]] = ...,
+    ) -> None: ...
+    @property
+    def max_bytes(self) -> int: ...
+    @property
+    def size(self) -> int: ...
+    def add(self, data: BytesLike, *, key: Optional[str] = ...) -> str: ...
+    def get(
+        self, key: str, accept_encoding: str | BytesLike
+    ) -> Optional[tuple[Optional[str], bytes]]: ...
+    def codings(self, key: str) -> Optional[list[str]]: ...
+    def remove(self, key: str) -> bool: ...
+    def clear(self) -> None: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, key: str) -> bool: ...
+
+class ZipStreamWriter:
+    def __init__(self) -> None: ...
+    @property
+    def offset(self) -> int: ...
+    def start_entry(
+        self,
+        name: str,
+        *,
+        method: Literal["store", "deflate", "zstd"] = ...,
+        level: Optional[int] = ...,
+        mtime: Optional[int] = ...,
+        zip64: bool = ...,
+    ) -> bytes: ...
+    def write(self, data: BytesLike) -> bytes: ...
+    def end_entry(self) -> bytes: ...
+    def add(
+        self,
+        name: str,
+        data: BytesLike,
+        *,
+        method: Literal["store", "deflate", "zstd"] = ...,
+        level: Optional[int] = ...,
+        mtime: Optional[int] = ...,
+    ) -> bytes: ...
+    def finish(self) -> bytes: ...
+
+class SeekableZstdReader:
+    def __init__(
+        self, source: BytesLike | BinaryIO, *, max_output_size: Optional[int] = ...
+    ) -> None: ...
+    @property
+    def size(self) -> int: ...
+    @property
+    def frame_count(self) -> int: ...
+    def read_range(self, start: int, end: int) -> bytes: ...
+
+class CompressionResponder:
+    def __init__(
+        self,
+        accept_encoding: BytesLike,
+        *,
+        minimum_size: int = ...,
+        content_types: Optional[Sequence[str]] = ...,
+        available: Optional[Sequence[str]] = ...,
+        policy: Optional[CompressionPolicy] = ...,
+    ) -> None: ...
+    @property
+    def encoding(self) -> Optional[str]: ...
+    def send(self, message: dict[str, Any]) -> list[dict[str, Any]]: ...
+
+def gzip_compress(
+    data: BytesLike,
+    level: int = ...,
+    *,
+    filename: Optional[str] = ...,
+    mtime: Optional[int] = ...,
+    comment: Optional[str] = ...,
+) -> bytes: ...
+
+def gzip_decompress(
+    data: BytesLike, *, max_output_size: Optional[int] = ..., multi_member: bool = ...
+) -> bytes: ...
+
+class GzipInfo:
+    @property
+    def filename(self) -> Optional[str]: ...
+    @property
+    def mtime(self) -> int: ...
+    @property
+    def os(self) -> int: ...
+    @property
+    def comment(self) -> Optional[str]: ...
+
+def gzip_info(data: BytesLike) -> GzipInfo: ...
+
+def deflate_compress(data: BytesLike, level: int = ..., *, raw: bool = ...) -> bytes: ...
+
+def deflate_decompress(data: BytesLike, *, max_output_size: Optional[int] = ...) -> bytes: ...
+
+def brotli_compress(
+    data: BytesLike, level: int = ..., *, params: Optional[BrotliParams] = ...
+) -> bytes: ...
+
+def brotli_decompress(data: BytesLike, *, max_output_size: Optional[int] = ...) -> bytes: ...
+
+def zstd_compress(
+    data: BytesLike,
+    level: int = ...,
+    *,
+    dictionary: Optional[ZstdDictionary] = ...,
+    params: Optional[ZstdParams] = ...,
+) -> bytes: ...
+
+def zstd_decompress(
+    data: BytesLike,
+    *,
+    max_output_size: Optional[int] = ...,
+    dictionary: Optional[ZstdDictionary] = ...,
+) -> bytes: ...
+
+class ZstdFrameInfo:
+    @property
+    def offset(self) -> int: ...
+    @property
+    def compressed_size(self) -> Optional[int]: ...
+    @property
+    def header_size(self) -> int: ...
+    @property
+    def skippable(self) -> bool: ...
+    @property
+    def content_size(self) -> Optional[int]: ...
+    @property
+    def window_size(self) -> int: ...
+    @property
+    def dictionary_id(self) -> Optional[int]: ...
+    @property
+    def has_checksum(self) -> bool: ...
+
+class ZstdFrameIterator:
+    def __iter__(self) -> ZstdFrameIterator: ...
+    def __next__(self) -> ZstdFrameInfo: ...
+
+def zstd_frame_info(data: BytesLike) -> ZstdFrameInfo: ...
+
+def iter_zstd_frames(data: BytesLike) -> ZstdFrameIterator: ...
+
+def zstd_train_dictionary(samples: Sequence[BytesLike], size: int) -> ZstdDictionary: ...
+
+def zstd_seekable_compress(
+    data: BytesLike, level: int = ..., *, frame_size: int = ..., checksum: bool = ...
+) -> bytes: ...
+
+def lz4_compress(data: BytesLike) -> bytes: ...
+
+def lz4_decompress(data: BytesLike, *, max_output_size: Optional[int] = ...) -> bytes: ...
+
+def snappy_compress(data: BytesLike, *, framed: bool = ...) -> bytes: ...
+
+def snappy_decompress(
+    data: BytesLike, *, framed: bool = ..., max_output_size: Optional[int] = ...
+) -> bytes: ...
+
+def decompress(
+    data: BytesLike, encoding: Optional[str] = ..., *, max_output_size: Optional[int] = ...
+) -> bytes: ...
+
+def compress_adaptive(
+    codec: str,
+    data: BytesLike,
+    level: Optional[int] = ...,
+    *,
+    content_type: Optional[str | BytesLike] = ...,
+    policy: Optional[CompressionPolicy] = ...,
+) -> tuple[bytes, bool]: ...
+
+def compress_into(
+    codec: str, data: BytesLike, out: bytearray | memoryview, level: Optional[int] = ...
+) -> int: ...
+
+async def acompress(codec: str, data: BytesLike, level: Optional[int] = ...) -> bytes: ...
+
+async def adecompress(
+    codec: Optional[str], data: BytesLike, *, max_output_size: Optional[int] = ...
+) -> bytes: ...
+
+def decompress_into(
+    codec: Optional[str],
+    data: BytesLike,
+    out: bytearray | memoryview,
+    *,
+    max_output_size: Optional[int] = ...,
+) -> int: ...
+
+def dcb_compress(data: BytesLike, dictionary: SharedDictionary, level: int = ...) -> bytes: ...
+
+def dcb_decompress(
+    data: BytesLike, dictionary: SharedDictionary, *, max_output_size: Optional[int] = ...
+) -> bytes: ...
+
+def dcz_compress(data: BytesLike, dictionary: SharedDictionary, level: int = ...) -> bytes: ...
+
+def dcz_decompress(
+    data: BytesLike, dictionary: SharedDictionary, *, max_output_size: Optional[int] = ...
+) -> bytes: ...
 
-def gzip_decompress(data: BytesLike) -> bytes: ...
+def match_available_dictionary(
+    available_dictionary: BytesLike, dictionaries: Sequence[SharedDictionary]
+) -> Optional[SharedDictionary]: ...
 
-def brotli_compress(data: BytesLike, level: int = ...) -> bytes: ...
+def set_default_max_output_size(max_output_size: Optional[int]) -> None: ...
 
-def brotli_decompress(data: BytesLike) -> bytes: ...
+def get_default_max_output_size() -> Optional[int]: ...
 
-def zstd_compress(data: BytesLike, level: int = ...) -> bytes: ...
+def negotiate(
+    accept_encoding: BytesLike, available: Optional[Sequence[str]] = ...
+) -> Optional[str]: ...
 
-def zstd_decompress(data: BytesLike) -> bytes: ...
+def negotiate_compressor(
+    accept_encoding: BytesLike, available: Optional[Sequence[str]] = ...
+) -> Optional[tuple[str, _Compressor]]: ...
@@ -1,6 +1,17 @@
 from __future__ import annotations
 
+import array
+import asyncio
+import hashlib
+import io
+import os
+import subprocess
+import sys
+import zipfile
+import zlib
+
 import fastcomp
+import pytest
 
 
 DATA = b"hello fastcomp"
@@ -19,3 +30,726 @@ def test_brotli_roundtrip() -> None:
 def test_zstd_roundtrip() -> None:
     compressed = fastcomp.zstd_compress(DATA)
     assert fastcomp.zstd_decompress(compressed) == DATA
+
+
+def test_gzip_compressor_streams_single_member() -> None:
+    payload = b'{"items": [' + b'{"id": 1, "name": "fastcomp"},' * 2000 + b"]}"
+    chunks = [payload[i : i + 4096] for i in range(0, len(payload), 4096)]
+    compressor = fastcomp.GzipCompressor()
+    streamed = b"".join(compressor.update(chunk) for chunk in chunks) + compressor.finish()
+    assert fastcomp.gzip_decompress(streamed) == payload
+    assert len(streamed) == len(fastcomp.gzip_compress(payload))
+
+
+def test_brotli_and_zstd_compressors_stream() -> None:
+    payload = b"data: " + b"x" * 10_000 + b"\n" + bytes(range(256)) * 40
+    chunks = [payload[i : i + 1000] for i in range(0, len(payload), 1000)]
+    for compressor, decompress in (
+        (fastcomp.BrotliCompressor(), fastcomp.brotli_decompress),
+        (fastcomp.ZstdCompressor(), fastcomp.zstd_decompress),
+    ):
+        streamed = b"".join(compressor.update(chunk) for chunk in chunks) + compressor.finish()
+        assert decompress(streamed) == payload
+
+
+def test_decompressors_stream_with_output_cap() -> None:
+    payload = b"fastcomp streaming " * 5000
+    for compress, decompressor in (
+        (fastcomp.gzip_compress, fastcomp.GzipDecompressor()),
+        (fastcomp.brotli_compress, fastcomp.BrotliDecompressor()),
+        (fastcomp.zstd_compress, fastcomp.ZstdDecompressor()),
+    ):
+        compressed = compress(payload) + b"trailing"
+        output = b""
+        for i in range(0, len(compressed), 100):
+            chunk = decompressor.decompress(compressed[i : i + 100], max_length=1024)
+            assert len(chunk) <= 1024
+            output += chunk
+        while not decompressor.eof:
+            assert not decompressor.needs_input
+            output += decompressor.decompress(b"", max_length=1024)
+        assert output == payload
+        assert decompressor.unused_data == b"trailing"
+
+
+def test_decompress_rejects_output_over_limit() -> None:
+    bomb = b"\0" * 1_000_000
+    for compress, decompress in (
+        (fastcomp.gzip_compress, fastcomp.gzip_decompress),
+        (fastcomp.brotli_compress, fastcomp.brotli_decompress),
+        (fastcomp.zstd_compress, fastcomp.zstd_decompress),
+    ):
+        compressed = compress(bomb)
+        assert decompress(compressed, max_output_size=len(bomb)) == bomb
+        with pytest.raises(fastcomp.DecompressionLimitExceeded):
+            decompress(compressed, max_output_size=len(bomb) - 1)
+
+    decompressor = fastcomp.ZstdDecompressor(max_output_size=1024)
+    with pytest.raises(fastcomp.DecompressionLimitExceeded):
+        decompressor.decompress(fastcomp.zstd_compress(bomb))
+
+    unlimited = fastcomp.GzipDecompressor(max_output_size=2**64 - 1)
+    assert unlimited.decompress(fastcomp.gzip_compress(DATA)) == DATA
+
+
+def test_default_max_output_size_applies_module_wide() -> None:
+    compressed = fastcomp.gzip_compress(b"a" * 4096)
+    fastcomp.set_default_max_output_size(1024)
+    try:
+        assert fastcomp.get_default_max_output_size() == 1024
+        with pytest.raises(fastcomp.DecompressionLimitExceeded):
+            fastcomp.gzip_decompress(compressed)
+        assert fastcomp.gzip_decompress(compressed, max_output_size=4096) == b"a" * 4096
+    finally:
+        fastcomp.set_default_max_output_size(None)
+    assert fastcomp.get_default_max_output_size() is None
+
+
+def test_deflate_accepts_zlib_and_raw_framing() -> None:
+    payload = b"deflate is still around " * 200
+    zlib_wrapped = fastcomp.deflate_compress(payload)
+    raw = fastcomp.deflate_compress(payload, raw=True)
+    assert zlib.decompress(zlib_wrapped) == payload
+    assert zlib.decompress(raw, -zlib.MAX_WBITS) == payload
+    for compressed in (zlib_wrapped, raw):
+        assert fastcomp.deflate_decompress(compressed) == payload
+        decompressor = fastcomp.DeflateDecompressor()
+        output = b"".join(decompressor.decompress(bytes([byte])) for byte in compressed)
+        assert output == payload
+        assert decompressor.eof
+
+    compressor = fastcomp.DeflateCompressor(raw=True)
+    streamed = compressor.update(payload[:100]) + compressor.update(payload[100:])
+    streamed += compressor.finish()
+    assert fastcomp.deflate_decompress(streamed) == payload
+
+    for build in (
+        lambda: fastcomp.deflate_compress(b"x", 42),
+        lambda: fastcomp.DeflateCompressor(10),
+        lambda: fastcomp.ZipStreamWriter().start_entry("a.txt", level=12),
+    ):
+        with pytest.raises(fastcomp.InvalidParameterError):
+            build()
+
+
+def test_negotiate_follows_weights_and_server_preference() -> None:
+    assert fastcomp.negotiate(b"gzip, deflate, br, zstd") == "br"
+    assert fastcomp.negotiate(b"gzip, br", available=["zstd", "gzip", "br"]) == "gzip"
+    assert fastcomp.negotiate(b"br;q=0.5, gzip") == "gzip"
+    assert fastcomp.negotiate(b"*;q=0.2, br;q=0") == "zstd"
+    assert fastcomp.negotiate(b"X-GZIP;q=0.5") == "gzip"
+    assert fastcomp.negotiate(b"gzip;q=0.5, identity") is None
+    assert fastcomp.negotiate(b"identity;q=0, *;q=0") is None
+    assert fastcomp.negotiate(b"") is None
+
+    coding, compressor = fastcomp.negotiate_compressor(b"gzip, zstd;q=0.9")
+    assert coding == "gzip"
+    body = compressor.update(DATA) + compressor.finish()
+    assert fastcomp.gzip_decompress(body) == DATA
+    assert fastcomp.negotiate_compressor(b"compress") is None
+    assert fastcomp.negotiate_compressor(b"identity", available=["identity", "gzip"]) is None
+    coding, _ = fastcomp.negotiate_compressor(b"gzip", available=["identity", "gzip"])
+    assert coding == "gzip"
+    with pytest.raises(fastcomp.InvalidParameterError, match="lz4"):
+        fastcomp.negotiate_compressor(b"gzip", available=["gzip", "lz4"])
+
+
+def _start(*headers: tuple[bytes, bytes]) -> dict:
+    return {"type": "http.response.start", "status": 200, "headers": list(headers)}
+
+
+def _body(body: bytes, more_body: bool = False) -> dict:
+    return {"type": "http.response.body", "body": body, "more_body": more_body}
+
+
+def test_compression_responder_streams_and_rewrites_headers() -> None:
+    responder = fastcomp.CompressionResponder(b"gzip, br", available=["gzip"])
+    start = _start((b"content-type", b"application/json"), (b"content-length", b"4000"))
+    assert responder.send(start) == []
+    first = responder.send(_body(b"[" + b"1," * 1000, more_body=True))
+    rest = responder.send(_body(b"1" * 999 + b"]"))
+    headers = dict(first[0]["headers"])
+    assert headers[b"content-encoding"] == b"gzip"
+    assert headers[b"vary"] == b"Accept-Encoding"
+    assert b"content-length" not in headers
+    assert responder.encoding == "gzip"
+    compressed = b"".join(message["body"] for message in first[1:] + rest)
+    assert fastcomp.gzip_decompress(compressed) == b"[" + b"1," * 1000 + b"1" * 999 + b"]"
+    with pytest.raises(fastcomp.InvalidParameterError, match="lz4"):
+        fastcomp.CompressionResponder(b"gzip", available=["gzip", "lz4"])
+
+
+def test_compression_responder_passes_through_ineligible_responses() -> None:
+    cases = [
+        (_start((b"content-type", b"image/png")), b"x" * 1000),
+        (_start((b"content-type", b"text/plain"), (b"content-encoding", b"br")), b"x" * 1000),
+        (
+            _start((b"content-type", b"text/plain"), (b"cache-control", b"no-transform")),
+            b"x" * 1000,
+        ),
+        (_start((b"content-type", b"text/plain")), b"tiny"),
+    ]
+    for start, body in cases:
+        responder = fastcomp.CompressionResponder(b"gzip")
+        messages = responder.send(start) + responder.send(_body(body))
+        assert messages[-1]["body"] == body
+        assert dict(messages[0]["headers"]).get(b"content-encoding") != b"gzip"
+        assert responder.encoding is None
+
+    # A non-body message while the start is held back goes out after it, uncompressed.
+    responder = fastcomp.CompressionResponder(b"gzip")
+    start = _start((b"content-type", b"text/plain"))
+    pathsend = {"type": "http.response.pathsend", "path": "/srv/index.txt"}
+    assert responder.send(start) == []
+    messages = responder.send(pathsend)
+    assert [message["type"] for message in messages] == ["http.response.start", pathsend["type"]]
+    assert b"content-encoding" not in dict(messages[0]["headers"])
+    assert responder.send(_body(b"x" * 1000)) == [_body(b"x" * 1000)]
+    assert responder.encoding is None
+
+
+def test_zstd_dictionary_shrinks_small_similar_documents() -> None:
+    samples = [
+        b'{"id": %d, "status": "active", "kind": "user", "email": "user%d@example.com"}' % (i, i)
+        for i in range(500)
+    ]
+    dictionary = fastcomp.zstd_train_dictionary(samples, 4096)
+    assert dictionary.dict_id
+    assert len(dictionary) == len(bytes(dictionary)) <= 4096
+
+    document = samples[42]
+    compressed = fastcomp.zstd_compress(document, dictionary=dictionary)
+    assert len(compressed) < len(fastcomp.zstd_compress(document))
+    assert fastcomp.zstd_decompress(compressed, dictionary=dictionary) == document
+
+    compressor = fastcomp.ZstdCompressor(dictionary=dictionary)
+    streamed = compressor.update(document) + compressor.finish()
+    decompressor = fastcomp.ZstdDecompressor(dictionary=dictionary)
+    assert decompressor.decompress(streamed) == document
+
+    with pytest.raises(fastcomp.InvalidParameterError):
+        fastcomp.ZstdDictionary(b"\x37\xa4\x30\xec" + b"\x01" * 40)
+    raw = fastcomp.ZstdDictionary(b"".join(samples[:50]))
+    assert raw.dict_id is None
+    compressed = fastcomp.zstd_compress(document, dictionary=raw)
+    assert fastcomp.zstd_decompress(compressed, dictionary=raw) == document
+
+
+def test_shared_dictionary_dcb_and_dcz_round_trip() -> None:
+    previous = b"<html><body>" + b"<p>shared boilerplate paragraph</p>" * 40 + b"</body></html>"
+    current = previous.replace(b"boilerplate", b"boilerplate!", 3)
+    dictionary = fastcomp.SharedDictionary(previous)
+    other = fastcomp.SharedDictionary(b"unrelated")
+    assert len(dictionary.hash) == 32
+    assert fastcomp.match_available_dictionary(
+        b" " + dictionary.available_dictionary, [other, dictionary]
+    ) is dictionary
+    assert fastcomp.match_available_dictionary(b":AAAA:", [dictionary]) is None
+
+    dcb = fastcomp.dcb_compress(current, dictionary)
+    assert dcb[:4] == b"\xffDCB" and dcb[4:36] == dictionary.hash
+    assert len(dcb) < len(fastcomp.brotli_compress(current))
+    assert fastcomp.dcb_decompress(dcb, dictionary) == current
+
+    dcz = fastcomp.dcz_compress(current, dictionary)
+    assert dcz[:8] == b"\x5e\x2a\x4d\x18\x20\x00\x00\x00" and dcz[8:40] == dictionary.hash
+    assert fastcomp.dcz_decompress(dcz, dictionary) == current
+    with pytest.raises(ValueError):
+        fastcomp.dcz_decompress(dcz, other)
+
+    # dcz uses the dictionary as raw content, even when it starts with the zstd dictionary magic.
+    lookalike = b"\x37\xa4\x30\xec" + bytes(range(256)) * 8
+    dictionary = fastcomp.SharedDictionary(lookalike)
+    dcz = fastcomp.dcz_compress(lookalike, dictionary)
+    assert len(dcz) < 40 + 64
+    assert fastcomp.dcz_decompress(dcz, dictionary) == lookalike
+
+    # Level 22 would pick a 128 MiB window; dcz stays within the 8 MiB browsers allow.
+    large = os.urandom(1 << 16) * 160
+    dcz = fastcomp.dcz_compress(large, dictionary, 22)
+    assert dcz[40 + 4] & 0x20 == 0 and dcz[40 + 5] == (23 - 10) << 3
+    assert fastcomp.dcz_decompress(dcz, dictionary) == large
+
+
+def test_flush_makes_events_decodable_before_finish() -> None:
+    events = [b"data: event %d\n\n" % i for i in range(3)]
+    cases = [
+        (fastcomp.GzipCompressor(), fastcomp.GzipDecompressor()),
+        (fastcomp.BrotliCompressor(), fastcomp.BrotliDecompressor()),
+        (fastcomp.ZstdCompressor(), fastcomp.ZstdDecompressor()),
+    ]
+    for compressor, decompressor in cases:
+        for mode, event in zip(["sync", "full", "sync"], events):
+            chunk = compressor.update(event) + compressor.flush(mode)
+            assert decompressor.decompress(chunk) == event
+        assert decompressor.decompress(compressor.finish()) == b""
+        assert decompressor.eof
+    gzip = fastcomp.GzipCompressor()
+    body = gzip.update(DATA) + gzip.flush("full") + gzip.finish()
+    assert zlib.decompress(body, 31) == DATA
+    with pytest.raises(ValueError):
+        gzip.flush()
+    with pytest.raises(ValueError):
+        fastcomp.ZstdCompressor().flush("partial")
+
+
+def test_decompress_detects_format_and_undoes_content_encoding_chain() -> None:
+    assert fastcomp.decompress(fastcomp.gzip_compress(DATA)) == DATA
+    assert fastcomp.decompress(fastcomp.zstd_compress(DATA)) == DATA
+    assert fastcomp.decompress(zlib.compress(DATA)) == DATA
+    with pytest.raises(ValueError):
+        fastcomp.decompress(fastcomp.brotli_compress(DATA))
+
+    chained = fastcomp.brotli_compress(fastcomp.gzip_compress(DATA))
+    assert fastcomp.decompress(chained, "gzip, br") == DATA
+    assert fastcomp.decompress(DATA, "identity") == DATA
+    with pytest.raises(fastcomp.DecompressionLimitExceeded):
+        fastcomp.decompress(chained, "gzip,br", max_output_size=4)
+    with pytest.raises(ValueError):
+        fastcomp.decompress(chained, "compress")
+
+
+def test_gzip_multi_member_and_header_metadata() -> None:
+    first = fastcomp.gzip_compress(b"one\n", filename="app.log", mtime=1700000000, comment="café")
+    concatenated = first + fastcomp.gzip_compress(b"two\n")
+    assert fastcomp.gzip_decompress(concatenated) == b"one\ntwo\n"
+    assert fastcomp.gzip_decompress(concatenated, multi_member=False) == b"one\n"
+    assert zlib.decompress(first, 31) == b"one\n"
+
+    info = fastcomp.gzip_info(concatenated)
+    assert (info.filename, info.mtime, info.os, info.comment) == (
+        "app.log",
+        1700000000,
+        255,
+        "café",
+    )
+    compressor = fastcomp.GzipCompressor(filename="stream.txt")
+    streamed = compressor.update(DATA) + compressor.finish()
+    assert fastcomp.gzip_info(streamed).filename == "stream.txt"
+    with pytest.raises(ValueError):
+        fastcomp.gzip_compress(DATA, filename="snow☃")
+    with pytest.raises(fastcomp.CorruptDataError):
+        fastcomp.gzip_info(b"not gzip")
+
+    # NUL padding between and after members is accepted, as by gzip.decompress.
+    padded = first + b"\0" * 3 + fastcomp.gzip_compress(b"two\n") + b"\0" * 512
+    assert fastcomp.gzip_decompress(padded) == b"one\ntwo\n"
+    with pytest.raises(fastcomp.CorruptDataError):
+        fastcomp.gzip_decompress(first + b"\0\0junk")
+    # A header cut off inside the file name or before it is truncated, not corrupt.
+    for cut in (5, 14):
+        with pytest.raises(fastcomp.TruncatedInputError):
+            fastcomp.gzip_info(first[:cut])
+        with pytest.raises(fastcomp.TruncatedInputError):
+            fastcomp.gzip_decompress(first[:cut])
+
+
+def test_zstd_params_apply_to_one_shot_and_streaming() -> None:
+    data = b"".join(b"row %d,exported,value\n" % i for i in range(20000))
+    params = fastcomp.ZstdParams(
+        workers=2, window_log=24, long_distance_matching=True, checksum=True
+    )
+    compressed = fastcomp.zstd_compress(data, params=params)
+    # Frame header descriptor: content size and checksum flags.
+    assert compressed[4] & 0x04 and compressed[4] & 0xC0
+    assert fastcomp.zstd_decompress(compressed) == data
+    bare = fastcomp.zstd_compress(data, params=fastcomp.ZstdParams(content_size=False))
+    assert not bare[4] & 0x04 and not bare[4] & 0xC0
+
+    compressor = fastcomp.ZstdCompressor(params=params)
+    streamed = compressor.update(data) + compressor.finish()
+    assert fastcomp.ZstdDecompressor().decompress(streamed) == data
+    # The largest window allowed is the largest the decoders accept by default.
+    widest = fastcomp.ZstdCompressor(params=fastcomp.ZstdParams(window_log=27))
+    streamed = widest.update(data) + widest.finish()
+    assert streamed[5] == (27 - 10) << 3
+    assert fastcomp.zstd_decompress(streamed) == data
+    assert fastcomp.ZstdDecompressor().decompress(streamed) == data
+    for window_log in (28, 40):
+        with pytest.raises(fastcomp.InvalidParameterError):
+            fastcomp.ZstdParams(window_log=window_log)
+
+
+def test_brotli_params_and_quality_validation() -> None:
+    asset = b"body { color: #333; margin: 0 auto; }\n" * 2000
+    params = fastcomp.BrotliParams(lgwin=24, mode="text", size_hint=len(asset))
+    compressed = fastcomp.brotli_compress(asset, 11, params=params)
+    assert fastcomp.brotli_decompress(compressed) == asset
+
+    fast = fastcomp.BrotliParams(lgwin=16, lgblock=16)
+    compressor = fastcomp.BrotliCompressor(1, params=fast)
+    streamed = compressor.update(asset) + compressor.finish()
+    assert fastcomp.brotli_decompress(streamed) == asset
+
+    with pytest.raises(ValueError):
+        fastcomp.brotli_compress(asset, 12)
+    with pytest.raises(ValueError):
+        fastcomp.BrotliCompressor(20)
+    with pytest.raises(ValueError):
+        fastcomp.BrotliParams(mode="binary")
+    with pytest.raises(ValueError):
+        fastcomp.BrotliParams(lgwin=30)
+
+
+def test_accepts_any_buffer_protocol_object() -> None:
+    import mmap
+
+    numbers = array.array("i", range(1000))
+    assert fastcomp.gzip_decompress(fastcomp.gzip_compress(numbers)) == numbers.tobytes()
+    compressed = fastcomp.zstd_compress(DATA)
+    assert fastcomp.zstd_decompress(memoryview(b"xx" + compressed)[2:]) == DATA
+    assert fastcomp.zstd_decompress(memoryview(compressed)) == DATA
+    assert fastcomp.zstd_decompress(memoryview(compressed).cast("B", (len(compressed),))) == DATA
+    assert fastcomp.brotli_decompress(bytearray(fastcomp.brotli_compress(DATA))) == DATA
+    with mmap.mmap(-1, len(compressed)) as mapped:
+        mapped.write(compressed)
+        assert fastcomp.decompress(mapped) == DATA
+    with pytest.raises(ValueError):
+        fastcomp.gzip_compress("text")
+
+
+def test_compress_into_and_decompress_into_reuse_buffers() -> None:
+    payload = DATA * 50
+    scratch = bytearray(4096)
+    for codec in ["gzip", "deflate", "br", "zstd"]:
+        written = fastcomp.compress_into(codec, payload, scratch)
+        compressed = bytes(scratch[:written])
+        plain = memoryview(bytearray(len(payload)))
+        assert fastcomp.decompress_into(codec, compressed, plain) == len(payload)
+        assert plain.tobytes() == payload
+
+    with pytest.raises(fastcomp.BufferTooSmall) as excinfo:
+        fastcomp.decompress_into(None, fastcomp.gzip_compress(payload), bytearray(10))
+    assert excinfo.value.args[1] == len(payload)
+    with pytest.raises(fastcomp.BufferTooSmall) as excinfo:
+        fastcomp.compress_into("zstd", payload, bytearray(4))
+    assert excinfo.value.args[1] == len(fastcomp.zstd_compress(payload))
+    with pytest.raises(TypeError):
+        fastcomp.compress_into("gzip", payload, memoryview(bytes(4096)))
+
+    # The output lands in the caller's memory, including slices and other buffer types.
+    target = bytearray(b"\xff" * 8192)
+    window = memoryview(target)[100:]
+    written = fastcomp.decompress_into("gzip", fastcomp.gzip_compress(payload), window)
+    assert target[:100] == b"\xff" * 100
+    assert target[100 : 100 + written] == payload
+    words = array.array("I", bytes(4096))
+    written = fastcomp.compress_into("br", payload, words)
+    assert fastcomp.brotli_decompress(words.tobytes()[:written]) == payload
+    # The buffer is released after the call, so it can be resized again.
+    scratch.extend(b"tail")
+    del scratch[:4]
+
+
+def test_acompress_runs_off_the_event_loop() -> None:
+    payload = b"<p>asynchronous</p>" * 5000
+
+    async def main() -> None:
+        results = await asyncio.gather(
+            *(fastcomp.acompress(codec, payload) for codec in ["gzip", "br", "zstd"])
+        )
+        for codec, compressed in zip(["gzip", "br", "zstd"], results):
+            assert fastcomp.decompress(compressed, codec) == payload
+            assert await fastcomp.adecompress(codec, compressed) == payload
+        with pytest.raises(fastcomp.DecompressionLimitExceeded):
+            await fastcomp.adecompress(None, results[0], max_output_size=10)
+
+        # A burst larger than the worker queue waits for free slots instead of failing.
+        burst = [fastcomp.acompress("gzip", DATA) for _ in range((os.cpu_count() or 4) * 64 + 50)]
+        skipped = fastcomp.acompress("zstd", payload)
+        skipped.cancel()
+        for compressed in await asyncio.gather(*burst):
+            assert fastcomp.gzip_decompress(compressed) == DATA
+        assert skipped.cancelled()
+
+    asyncio.run(main())
+
+
+def test_interpreter_exits_cleanly_with_async_jobs_in_flight() -> None:
+    script = (
+        "import asyncio, fastcomp\n"
+        "async def main():\n"
+        "    for _ in range(64):\n"
+        "        fastcomp.acompress('gzip', b'x' * 1_000_000)\n"
+        "asyncio.run(main())\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.pathsep.join(sys.path))
+    result = subprocess.run(
+        [sys.executable, "-c", script], env=env, capture_output=True, timeout=60
+    )
+    assert result.returncode == 0, result.stderr
+    assert result.stderr == b""
+
+
+def test_lz4_and_snappy_one_shot_and_streaming() -> None:
+    payload = DATA * 5000
+    lz4 = fastcomp.lz4_compress(payload)
+    framed = fastcomp.snappy_compress(payload)
+    raw = fastcomp.snappy_compress(payload, framed=False)
+    assert fastcomp.lz4_decompress(lz4) == payload
+    assert fastcomp.snappy_decompress(framed) == payload
+    assert fastcomp.snappy_decompress(raw, framed=False) == payload
+    assert fastcomp.decompress(lz4) == fastcomp.decompress(framed) == payload
+    with pytest.raises(fastcomp.DecompressionLimitExceeded):
+        fastcomp.snappy_decompress(raw, framed=False, max_output_size=100)
+
+    for compressor, decompressor, compressed in [
+        (fastcomp.Lz4Compressor(), fastcomp.Lz4Decompressor(), lz4),
+        (fastcomp.SnappyCompressor(), fastcomp.SnappyDecompressor(), framed),
+    ]:
+        streamed = compressor.update(payload[:1000]) + compressor.flush()
+        streamed += compressor.update(payload[1000:]) + compressor.finish()
+        assert fastcomp.decompress(streamed) == payload
+        chunks = [compressed[i : i + 777] for i in range(0, len(compressed), 777)]
+        assert b"".join(decompressor.decompress(chunk) for chunk in chunks) == payload
+
+    lz4_decompressor = fastcomp.Lz4Decompressor()
+    assert lz4_decompressor.decompress(lz4 + b"tail") == payload
+    assert lz4_decompressor.eof and lz4_decompressor.unused_data == b"tail"
+    with pytest.raises(ValueError):
+        fastcomp.Lz4Decompressor().decompress(lz4[:-1] + bytes([lz4[-1] ^ 1]))
+
+    # Skippable frames and chunks are stepped over by both paths, and never count as output.
+    skippable = (0x184D2A53).to_bytes(4, "little") + (1 << 20).to_bytes(4, "little")
+    skippable += bytes(1 << 20)
+    assert fastcomp.lz4_decompress(skippable + lz4) == payload
+    decompressor = fastcomp.Lz4Decompressor(max_output_size=len(payload))
+    chunks = [skippable[i : i + 65536] for i in range(0, len(skippable), 65536)]
+    assert b"".join(decompressor.decompress(chunk) for chunk in chunks + [lz4]) == payload
+    with pytest.raises(fastcomp.TruncatedInputError):
+        fastcomp.lz4_decompress(skippable[:-1])
+    identifier = framed[:10]
+    padded = identifier + b"\xfe" + (70000).to_bytes(3, "little") + bytes(70000)
+    padded += b"\x80\x03\x00\x00abc" + framed[10:]
+    assert fastcomp.snappy_decompress(padded) == payload
+    chunks = [padded[i : i + 777] for i in range(0, len(padded), 777)]
+    decompressor = fastcomp.SnappyDecompressor()
+    assert b"".join(decompressor.decompress(chunk) for chunk in chunks) == payload
+    for bad in [b"\xfe" + (76491).to_bytes(3, "little"), b"\x02\x00\x00\x00"]:
+        with pytest.raises(ValueError):
+            fastcomp.snappy_decompress(identifier + bad)
+        with pytest.raises(ValueError):
+            fastcomp.SnappyDecompressor().decompress(identifier + bad)
+
+
+def test_compress_adaptive_skips_incompressible_payloads() -> None:
+    noise = os.urandom(64 * 1024)
+    output, compressed = fastcomp.compress_adaptive("gzip", noise)
+    assert output is noise and not compressed
+    text = b"<li>adaptive</li>" * 2000
+    output, compressed = fastcomp.compress_adaptive("zstd", text, content_type="text/html")
+    assert compressed and fastcomp.zstd_decompress(output) == text
+    with pytest.raises(fastcomp.InvalidParameterError):
+        fastcomp.compress_adaptive("bogus", noise)
+    with pytest.raises(fastcomp.InvalidParameterError):
+        fastcomp.compress_adaptive("gzip", noise, 99)
+
+    policy = fastcomp.CompressionPolicy(
+        {"image/": "never", "image/svg+xml": "always"}, max_entropy=7.0
+    )
+    assert policy.decide(b"image/png") == "never"
+    assert policy.decide("image/SVG+xml; charset=utf-8") == "always"
+    assert policy.decide("application/json") == "auto"
+    assert not fastcomp.compress_adaptive("br", text, content_type="image/png", policy=policy)[1]
+    assert not policy.should_compress(noise) and policy.should_compress(text)
+
+    responder = fastcomp.CompressionResponder(
+        b"gzip", content_types=["application/"], policy=policy
+    )
+    start = _start((b"content-type", b"application/octet-stream"))
+    messages = responder.send(start) + responder.send(_body(noise))
+    assert messages[-1]["body"] == noise
+    assert responder.encoding is None
+
+
+def test_permessage_deflate_negotiates_and_round_trips() -> None:
+    server = fastcomp.PerMessageDeflate(client_max_window_bits=12)
+    declined = "permessage-deflate; server_max_window_bits=10, x-webkit-deflate-frame"
+    assert fastcomp.PerMessageDeflate().negotiate(declined) is None
+    offer = (
+        b"permessage-deflate; server_max_window_bits=10, "
+        b"permessage-deflate; client_max_window_bits"
+    )
+    assert server.negotiate(offer) == "permessage-deflate; client_max_window_bits=12"
+    assert server.client_max_window_bits == 12 and not server.server_no_context_takeover
+
+    message = b'{"event": "tick", "price": 101.25}' * 20
+    first = server.compress(message)
+    assert not first.endswith(b"\x00\x00\xff\xff")
+    assert zlib.decompressobj(-15).decompress(first + b"\x00\x00\xff\xff") == message
+    assert len(server.compress(message)) < len(first)
+
+    client = fastcomp.PerMessageDeflate(is_server=False, server_no_context_takeover=True)
+    assert client.offer() == "permessage-deflate; server_no_context_takeover"
+    with pytest.raises(ValueError):
+        client.accept("permessage-deflate")
+    server = fastcomp.PerMessageDeflate()
+    client.accept(server.negotiate(client.offer()))
+    assert client.enabled and client.server_no_context_takeover
+    for _ in range(3):
+        payload = server.compress(message)
+        assert len(payload) == len(first)
+        assert client.decompress(payload) == message
+        assert server.decompress(client.compress(message)) == message
+
+    limited = fastcomp.PerMessageDeflate(is_server=False, max_output_size=len(message))
+    server = fastcomp.PerMessageDeflate()
+    limited.accept(server.negotiate(limited.offer()))
+    assert limited.decompress(server.compress(message)) == message
+    with pytest.raises(fastcomp.DecompressionLimitExceeded):
+        limited.decompress(server.compress(b"\x00" * 10_000_000))
+
+
+def test_precompressed_store_serves_cached_variants_with_lru_eviction() -> None:
+    script = b"function tick() { return Date.now(); }\n" * 400
+    store = fastcomp.PrecompressedStore(25_000, codings=["br", "gzip"], levels={"br": 9})
+    with pytest.raises(fastcomp.InvalidParameterError, match="snappy"):
+        fastcomp.PrecompressedStore(1000, codings=["gzip", "snappy"])
+    with pytest.raises(fastcomp.InvalidParameterError, match="zstd"):
+        fastcomp.PrecompressedStore(1000, codings=["gzip"], levels={"zstd": 3})
+    fast = fastcomp.PrecompressedStore(25_000, codings=["gzip"], levels={"GZIP": 1})
+    assert fast.get(fast.add(script), "gzip")[1] == fastcomp.gzip_compress(script, 1)
+    key = store.add(script)
+    assert key == hashlib.sha256(script).hexdigest()
+    assert store.codings(key) == ["br", "gzip"]
+    coding, body = store.get(key, "gzip, br")
+    assert coding == "br" and fastcomp.brotli_decompress(body) == script
+    coding, body = store.get(key, b"gzip;q=1, br;q=0.5")
+    assert coding == "gzip" and fastcomp.gzip_decompress(body) == script
+    assert store.get(key, "identity") == (None, script)
+    assert store.get("missing", "br") is None
+
+    store.add(b"a" * 4000 + script[:1000], key='"v2"')
+    store.get(key, "br")
+    store.add(b"b" * 4000 + script[:1000], key='"v3"')
+    assert key in store and '"v2"' not in store and '"v3"' in store
+    assert store.size <= store.max_bytes
+    assert store.remove(key) and len(store) == 1
+
+
+def test_zip_stream_writer_builds_a_readable_archive() -> None:
+    report = b"id,total\n" + b"".join(b"%d,%d\n" % (i, i * 7) for i in range(20000))
+    writer = fastcomp.ZipStreamWriter()
+    chunks = [writer.start_entry("exports/report.csv", mtime=1_700_000_000)]
+    chunks += [writer.write(report[i : i + 10_000]) for i in range(0, len(report), 10_000)]
+    chunks.append(writer.end_entry())
+    chunks.append(writer.add("exports/", b""))
+    chunks.append(writer.add("notes/résumé.txt", b"plain text", method="store"))
+    chunks.append(writer.add("data.json", b'{"ok": true}' * 100, method="zstd"))
+    chunks.append(writer.start_entry("big.bin", zip64=True))
+    chunks.append(writer.write(b"\0" * 100_000))
+    chunks.append(writer.end_entry())
+    chunks.append(writer.finish())
+    archive = b"".join(chunks)
+    assert writer.offset == len(archive)
+    with pytest.raises(ValueError):
+        writer.add("late.txt", b"")
+
+    with zipfile.ZipFile(io.BytesIO(archive)) as zf:
+        names = [info.filename for info in zf.infolist()]
+        assert names[1:] == ["exports/", "notes/résumé.txt", "data.json", "big.bin"]
+        assert zf.read("exports/report.csv") == report
+        assert zf.getinfo("exports/report.csv").date_time == (2023, 11, 14, 22, 13, 20)
+        assert zf.getinfo("exports/").is_dir()
+        assert zf.read("notes/résumé.txt") == b"plain text"
+        assert zf.read("big.bin") == b"\0" * 100_000
+        info = zf.getinfo("data.json")
+        assert info.compress_type == 93
+        start = info.header_offset + 30 + len(info.filename)
+        raw = archive[start : start + info.compress_size]
+        assert fastcomp.zstd_decompress(raw) == b'{"ok": true}' * 100
+
+
+def test_seekable_zstd_reads_ranges_without_the_prefix() -> None:
+    log = b"".join(b"%08d GET /api/items/%d 200\n" % (i, i % 97) for i in range(40000))
+    blob = fastcomp.zstd_seekable_compress(log, frame_size=64 * 1024)
+    assert fastcomp.zstd_decompress(blob) == log
+
+    compressor = fastcomp.ZstdSeekableCompressor(frame_size=64 * 1024, checksum=False)
+    streamed = b"".join(compressor.update(log[i : i + 50_000]) for i in range(0, len(log), 50_000))
+    streamed += compressor.finish()
+
+    for source in (blob, io.BytesIO(streamed)):
+        reader = fastcomp.SeekableZstdReader(source)
+        assert reader.size == len(log)
+        assert reader.frame_count == -(-len(log) // (64 * 1024))
+        for start, end in [(0, 10), (65_530, 65_540), (500_000, 700_000), (len(log) - 5, 10**9)]:
+            assert reader.read_range(start, end) == log[start:end]
+        assert reader.read_range(10, 10) == b""
+
+    corrupt = bytearray(blob)
+    corrupt[-20] ^= 0xFF
+    with pytest.raises(ValueError):
+        fastcomp.SeekableZstdReader(corrupt).read_range(len(log) - 100, len(log))
+    with pytest.raises(ValueError):
+        fastcomp.SeekableZstdReader(fastcomp.zstd_compress(log))
+
+
+def test_zstd_frame_info_and_frame_iteration() -> None:
+    first = fastcomp.zstd_compress(DATA, params=fastcomp.ZstdParams(checksum=True))
+    info = fastcomp.zstd_frame_info(first[:18])
+    assert (info.content_size, info.has_checksum, info.dictionary_id) == (len(DATA), True, None)
+    assert info.window_size >= len(DATA) and info.compressed_size is None
+
+    samples = [b'{"user": %d, "event": "login", "ok": true}' % i for i in range(500)]
+    dictionary = fastcomp.zstd_train_dictionary(samples, 2048)
+    second = fastcomp.zstd_compress(samples[0], dictionary=dictionary)
+    compressor = fastcomp.ZstdCompressor(params=fastcomp.ZstdParams(content_size=False))
+    third = compressor.update(DATA) + compressor.finish()
+    stream = first + second + fastcomp.zstd_seekable_compress(DATA, frame_size=len(DATA)) + third
+
+    frames = list(fastcomp.iter_zstd_frames(stream))
+    assert [frame.skippable for frame in frames] == [False, False, False, True, False]
+    assert frames[1].dictionary_id is not None and frames[4].content_size is None
+    assert frames[4].offset + frames[4].compressed_size == len(stream)
+    chunks = [stream[f.offset : f.offset + f.compressed_size] for f in frames if not f.skippable]
+    assert fastcomp.zstd_decompress(chunks[0]) == DATA
+    assert fastcomp.zstd_decompress(chunks[1], dictionary=dictionary) == samples[0]
+    with pytest.raises(ValueError):
+        list(fastcomp.iter_zstd_frames(stream[:-3]))
+    with pytest.raises(ValueError):
+        fastcomp.zstd_frame_info(b"not zstd")
+
+
+def test_typed_errors_report_codec_consumed_and_partial_output() -> None:
+    payload = b"".join(b"line %d of the access log\n" % i for i in range(20_000))
+    for codec, compress, decompress in [
+        ("gzip", fastcomp.gzip_compress, fastcomp.gzip_decompress),
+        ("deflate", fastcomp.deflate_compress, fastcomp.deflate_decompress),
+        ("zstd", fastcomp.zstd_compress, fastcomp.zstd_decompress),
+    ]:
+        compressed = compress(payload)
+        with pytest.raises(fastcomp.TruncatedInputError) as excinfo:
+            decompress(compressed[: len(compressed) // 2])
+        error = excinfo.value
+        assert error.codec == codec and error.consumed == len(compressed) // 2
+        assert error.partial_output and payload.startswith(error.partial_output)
+
+    with pytest.raises(fastcomp.CorruptDataError) as corrupt:
+        fastcomp.gzip_decompress(b"\x1f\x8b\x08\x00" + b"\xff" * 32)
+    assert corrupt.value.codec == "gzip"
+    with pytest.raises(fastcomp.InvalidParameterError):
+        fastcomp.ZstdParams(window_log=40)
+    with pytest.raises(fastcomp.InvalidParameterError):
+        fastcomp.decompress(DATA, "compress")
+
+    async def acompress_bad_level() -> bytes:
+        return await fastcomp.acompress("gzip", DATA, 12)
+
+    for build in (
+        lambda: fastcomp.gzip_compress(DATA, 50),
+        lambda: fastcomp.GzipCompressor(50),
+        lambda: fastcomp.brotli_compress(DATA, 12),
+        lambda: fastcomp.zstd_compress(DATA, 99),
+        lambda: fastcomp.ZstdCompressor(99),
+        lambda: fastcomp.zstd_seekable_compress(DATA, 99),
+        lambda: fastcomp.compress_into("gzip", DATA, bytearray(64), 12),
+        lambda: fastcomp.compress_adaptive("zstd", DATA * 100, 99),
+        lambda: asyncio.run(acompress_bad_level()),
+        lambda: fastcomp.PerMessageDeflate(level=20),
+        lambda: fastcomp.ZipStreamWriter().start_entry("a.txt", method="zstd", level=99),
+    ):
+        with pytest.raises(fastcomp.InvalidParameterError):
+            build()
+    for error in (fastcomp.TruncatedInputError, fastcomp.DecompressionLimitExceeded):
+        assert issubclass(error, fastcomp.CompressionError) and issubclass(error, ValueError)
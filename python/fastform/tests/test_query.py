@@ -6,3 +6,10 @@ import fastform
 def test_parse_query_decodes_pairs() -> None:
     result = fastform.parse_query(b"foo=bar&baz=qux")
     assert result == [(b"foo", b"bar"), (b"baz", b"qux")]
+
+
+def test_parse_query_accepts_buffer_protocol_objects() -> None:
+    expected = fastform.parse_query(b"foo=bar&baz=qux")
+    assert fastform.parse_query(bytearray(b"foo=bar&baz=qux")) == expected
+    assert fastform.parse_query(memoryview(b"?foo=bar&baz=qux")[1:]) == expected
+    assert fastform.parse_query(memoryview(b"foo=bar&baz=qux")) == expected
@@ -10,3 +10,11 @@ def test_round_trip_matches_stdlib() -> None:
     encoded = fastjson.dumps(payload)
     assert json.loads(encoded) == payload
     assert fastjson.loads(encoded) == payload
+
+
+def test_loads_accepts_buffer_protocol_objects() -> None:
+    encoded = b'{"id": 1, "tags": ["a", "b"]}'
+    expected = {"id": 1, "tags": ["a", "b"]}
+    assert fastjson.loads(bytearray(encoded), allow_nan=False) == expected
+    assert fastjson.loads(memoryview(b"  " + encoded)[2:], allow_nan=False) == expected
+    assert fastjson.loads(memoryview(encoded), allow_nan=False) == expected